@@ -0,0 +1,141 @@
+//! The receiver side of the AFHDS2A bind handshake
+//!
+//! A transmitter in bind mode alternates between the two [`BIND_CHANNELS`], announcing itself
+//! with bind packets addressed to [`UNKNOWN_RECEIVER_ID`]. The receiver answers each of these
+//! with its own ID, and once the transmitter has picked up that ID it sends a final few bind
+//! packets addressed to the receiver before switching over to sending sticks packets.
+
+use crate::packet::{
+    BindPacket, BindPacketType, NUM_BIND_OPTIONS, NUM_HOP_CHANNELS, UNKNOWN_RECEIVER_ID,
+};
+
+/// The channels a transmitter sends bind packets on
+pub const BIND_CHANNELS: [u8; 2] = [0x0D, 0x8C];
+
+/// How long to listen on each bind channel before switching to the other
+pub const BIND_LISTEN_US: u32 = 20_000;
+
+/// The stage byte sent in the receiver's replies to the transmitter
+const REPLY_STAGE: u8 = 0x01;
+
+/// Everything learnt about a transmitter while binding, needed to receive from it afterwards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BindResult {
+    /// The ID of the transmitter that was bound
+    pub transmitter_id: u32,
+    /// The channels the transmitter hops between
+    pub hop_table: [u8; NUM_HOP_CHANNELS],
+    /// The receiver option bytes sent by the transmitter
+    pub options: [u8; NUM_BIND_OPTIONS],
+}
+
+/// What the driver should do after a bind packet has been handled
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BindAction {
+    /// Nothing to do, keep listening
+    None,
+    /// Transmit the provided reply on the current channel, then keep listening
+    Reply(BindPacket),
+    /// The bind is complete
+    Complete(BindResult),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Waiting to hear from a transmitter in bind mode
+    Listening,
+    /// Replying to the transmitter until it addresses us directly
+    Responding { transmitter_id: u32 },
+    /// Bound to a transmitter
+    Complete(BindResult),
+}
+
+/// A state machine for the receiver side of the bind handshake
+///
+/// The binder does not perform any IO itself. The driver listens on [`channel`](Self::channel)
+/// for up to [`BIND_LISTEN_US`], handing any bind packet received to
+/// [`handle_packet`](Self::handle_packet) and reporting a timeout with
+/// [`handle_timeout`](Self::handle_timeout).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binder {
+    receiver_id: u32,
+    channel_index: usize,
+    state: State,
+}
+
+impl Binder {
+    /// Create a new [`Binder`] that will bind using the provided receiver ID
+    pub const fn new(receiver_id: u32) -> Self {
+        Self {
+            receiver_id,
+            channel_index: 0,
+            state: State::Listening,
+        }
+    }
+
+    /// The ID this receiver sends to the transmitter
+    pub const fn receiver_id(&self) -> u32 {
+        self.receiver_id
+    }
+
+    /// The channel the receiver should currently be listening on
+    pub const fn channel(&self) -> u8 {
+        BIND_CHANNELS[self.channel_index]
+    }
+
+    /// The result of the bind, once it is complete
+    pub const fn result(&self) -> Option<&BindResult> {
+        match &self.state {
+            State::Complete(result) => Some(result),
+            _ => None,
+        }
+    }
+
+    /// Handle a bind packet received from a transmitter
+    pub fn handle_packet(&mut self, packet: &BindPacket) -> BindAction {
+        let transmitter_id = match self.state {
+            State::Complete(_) => return BindAction::None,
+            State::Responding { transmitter_id } => {
+                // Stick with the first transmitter we heard from
+                if packet.transmitter_id() != transmitter_id {
+                    return BindAction::None;
+                }
+                transmitter_id
+            }
+            State::Listening => packet.transmitter_id(),
+        };
+
+        if packet.receiver_id() == self.receiver_id {
+            let result = BindResult {
+                transmitter_id,
+                hop_table: *packet.hop_table(),
+                options: *packet.options(),
+            };
+            self.state = State::Complete(result);
+            BindAction::Complete(result)
+        } else if packet.receiver_id() == UNKNOWN_RECEIVER_ID {
+            self.state = State::Responding { transmitter_id };
+            BindAction::Reply(BindPacket::new(
+                BindPacketType::Bind2,
+                transmitter_id,
+                self.receiver_id,
+                REPLY_STAGE,
+                [0xFF; NUM_HOP_CHANNELS],
+                [0xFF; NUM_BIND_OPTIONS],
+            ))
+        } else {
+            // The transmitter is binding with a different receiver
+            BindAction::None
+        }
+    }
+
+    /// Handle nothing being received on the current channel for [`BIND_LISTEN_US`]
+    pub fn handle_timeout(&mut self) {
+        if !matches!(self.state, State::Complete(_)) {
+            self.channel_index = (self.channel_index + 1) % BIND_CHANNELS.len();
+        }
+    }
+}
@@ -0,0 +1,49 @@
+/// The channels the VCO bank is calibrated against, the two ends of the usable band
+pub const VCO_CALIBRATION_CHANNELS: [u8; 2] = [0x00, 0xA0];
+
+/// Recommended manual VCO current from the A7105 datasheet
+pub(crate) const VCO_CURRENT: u8 = 0x03;
+
+/// Upper VCO tuning voltage threshold used for the VCO bank calibration (0x26 = 0x3B)
+pub(crate) const VCO_UPPER_THRESHOLD: u8 = 0b111;
+
+/// Lower VCO tuning voltage threshold used for the VCO bank calibration (0x26 = 0x3B)
+pub(crate) const VCO_LOWER_THRESHOLD: u8 = 0b011;
+
+/// How long to wait between polls of the auto-clearing calibration bits
+pub(crate) const POLL_INTERVAL_US: u32 = 10;
+
+/// How many times to poll the calibration bits before giving up, 1ms in total
+pub(crate) const POLL_ATTEMPTS: u32 = 100;
+
+/// The results of a successful calibration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CalibrationReport {
+    /// The IF filter bank value selected by the auto calibration
+    pub if_filter_bank: u8,
+    /// The VCO current in use after calibration
+    pub vco_current: u8,
+    /// The VCO bank selected for each of the [`VCO_CALIBRATION_CHANNELS`]
+    pub vco_bank: [u8; 2],
+}
+
+/// A step of the calibration sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CalibrationStage {
+    /// IF filter bank calibration
+    IfFilterBank,
+    /// VCO bank calibration on the given channel
+    VcoBank { channel: u8 },
+}
+
+/// The reasons that [`calibrate`](crate::Afhds2::calibrate) can fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CalibrationError {
+    /// The radio never cleared the calibration bit for the given stage
+    Timeout(CalibrationStage),
+    /// The radio reported that the given stage failed to calibrate
+    Failed(CalibrationStage),
+}
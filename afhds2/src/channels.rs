@@ -0,0 +1,221 @@
+use crate::packet::NUM_CONTROL_CHANNELS;
+
+/// The most control channels sent by any AFHDS2A transmitter
+pub const MAX_CHANNELS: usize = 18;
+
+/// The pulse width of a channel at one end of its travel, in microseconds
+pub const MIN_US: u16 = 1000;
+
+/// The pulse width of a centered channel, in microseconds
+pub const CENTER_US: u16 = 1500;
+
+/// The pulse width of a channel at the other end of its travel, in microseconds
+pub const MAX_US: u16 = 2000;
+
+/// The magnitude of a [normalized](Channels::normalized) channel at either end of its travel
+pub const NORMALIZED_MAX: i16 = 1000;
+
+/// How many control channels the transmitter sends
+///
+/// Every AFHDS2A sticks packet has room for 14 channel words. Radios that send 16 or 18 channels
+/// limit each word to 12 bits, packing the extra channels into the upper bits of the first words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChannelCount {
+    #[default]
+    Fourteen,
+    Sixteen,
+    Eighteen,
+}
+
+impl ChannelCount {
+    /// The number of channels
+    pub const fn num_channels(self) -> usize {
+        match self {
+            Self::Fourteen => 14,
+            Self::Sixteen => 16,
+            Self::Eighteen => 18,
+        }
+    }
+
+    /// The bits of each channel word that hold the channel's own value, the rest being used for
+    /// the extra channels
+    pub(crate) const fn value_mask(self) -> u16 {
+        match self {
+            Self::Fourteen => u16::MAX,
+            Self::Sixteen | Self::Eighteen => 0x0FFF,
+        }
+    }
+
+    /// The channel count with the provided number of channels, if it is one that is supported
+    pub const fn from_len(len: usize) -> Option<Self> {
+        match len {
+            14 => Some(Self::Fourteen),
+            16 => Some(Self::Sixteen),
+            18 => Some(Self::Eighteen),
+            _ => None,
+        }
+    }
+}
+
+/// The value of every control channel, as pulse widths in microseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Channels {
+    count: ChannelCount,
+    values: [u16; MAX_CHANNELS],
+}
+
+impl Channels {
+    /// Create a new [`Channels`] with every channel centered
+    pub const fn new(count: ChannelCount) -> Self {
+        let mut values = [0; MAX_CHANNELS];
+        let mut i = 0;
+        while i < count.num_channels() {
+            values[i] = CENTER_US;
+            i += 1;
+        }
+        Self { count, values }
+    }
+
+    /// Create a new [`Channels`] from the raw value of every channel
+    ///
+    /// Returns `None` unless there are 14, 16, or 18 values.
+    pub fn from_slice(values: &[u16]) -> Option<Self> {
+        let count = ChannelCount::from_len(values.len())?;
+        let mut channels = Self::new(count);
+        channels.values[..values.len()].copy_from_slice(values);
+        Some(channels)
+    }
+
+    /// Unpack the channels from the words of a sticks packet
+    pub(crate) fn from_words(words: &[u16; NUM_CONTROL_CHANNELS], count: ChannelCount) -> Self {
+        let mut channels = Self::new(count);
+        if count == ChannelCount::Fourteen {
+            channels.values[..NUM_CONTROL_CHANNELS].copy_from_slice(words);
+            return channels;
+        }
+
+        for (value, word) in channels.values.iter_mut().zip(words) {
+            *value = word & count.value_mask();
+        }
+        for extra in 0..count.num_channels() - NUM_CONTROL_CHANNELS {
+            // Each extra channel is spread across the top nibble of three words, low bits first
+            let nibbles = &words[extra * 3..extra * 3 + 3];
+            channels.values[NUM_CONTROL_CHANNELS + extra] = nibbles
+                .iter()
+                .enumerate()
+                .fold(0, |value, (i, word)| value | (word >> 12) << (i * 4));
+        }
+        channels
+    }
+
+    /// Pack the channels into the words of a sticks packet
+    pub(crate) fn to_words(self) -> [u16; NUM_CONTROL_CHANNELS] {
+        let mut words = [0; NUM_CONTROL_CHANNELS];
+        words.copy_from_slice(&self.values[..NUM_CONTROL_CHANNELS]);
+        if self.count == ChannelCount::Fourteen {
+            return words;
+        }
+
+        for word in &mut words {
+            *word &= self.count.value_mask();
+        }
+        for (extra, value) in self.values[NUM_CONTROL_CHANNELS..self.len()]
+            .iter()
+            .enumerate()
+        {
+            for i in 0..3 {
+                words[extra * 3 + i] |= ((value >> (i * 4)) & 0x0F) << 12;
+            }
+        }
+        words
+    }
+
+    /// How many channels there are
+    pub const fn count(&self) -> ChannelCount {
+        self.count
+    }
+
+    /// The number of channels
+    pub const fn len(&self) -> usize {
+        self.count.num_channels()
+    }
+
+    /// Always false, there are at least 14 channels
+    pub const fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// The raw value of every channel
+    pub fn as_slice(&self) -> &[u16] {
+        &self.values[..self.len()]
+    }
+
+    /// The raw value of the channel at the provided index, if it exists
+    pub fn raw(&self, index: usize) -> Option<u16> {
+        self.as_slice().get(index).copied()
+    }
+
+    /// Set the raw value of the channel at the provided index, returning false if it does not
+    /// exist
+    pub fn set(&mut self, index: usize, value: u16) -> bool {
+        match self.values[..self.count.num_channels()].get_mut(index) {
+            Some(channel) => {
+                *channel = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The pulse width of the channel at the provided index, clamped to [`MIN_US`]..=[`MAX_US`]
+    pub fn micros(&self, index: usize) -> Option<u16> {
+        self.raw(index).map(|value| value.clamp(MIN_US, MAX_US))
+    }
+
+    /// The position of the channel at the provided index, from -[`NORMALIZED_MAX`] at
+    /// [`MIN_US`] to [`NORMALIZED_MAX`] at [`MAX_US`]
+    pub fn normalized(&self, index: usize) -> Option<i16> {
+        // Each microsecond either side of center is two steps
+        self.micros(index)
+            .map(|micros| (micros as i16 - CENTER_US as i16) * 2)
+    }
+
+    /// The [normalized](Self::normalized) position of the channel at the provided index, with
+    /// anything within `deadband` of center reported as zero
+    ///
+    /// The remaining travel is scaled back up so that both ends still reach
+    /// [`NORMALIZED_MAX`].
+    pub fn normalized_with_deadband(&self, index: usize, deadband: i16) -> Option<i16> {
+        let deadband = deadband.clamp(0, NORMALIZED_MAX - 1);
+        self.normalized(index).map(|position| {
+            if position.abs() <= deadband {
+                return 0;
+            }
+            let travel = position.abs() - deadband;
+            let scaled = travel as i32 * NORMALIZED_MAX as i32 / (NORMALIZED_MAX - deadband) as i32;
+            scaled as i16 * position.signum()
+        })
+    }
+
+    /// Returns true if the raw value of the channel at the provided index is within
+    /// [`MIN_US`]..=[`MAX_US`]
+    pub fn is_in_range(&self, index: usize) -> bool {
+        self.raw(index)
+            .is_some_and(|value| (MIN_US..=MAX_US).contains(&value))
+    }
+
+    /// The index of every channel with a raw value outside of [`MIN_US`]..=[`MAX_US`]
+    pub fn out_of_range(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len()).filter(|&index| !self.is_in_range(index))
+    }
+}
+
+impl Default for Channels {
+    fn default() -> Self {
+        Self::new(ChannelCount::default())
+    }
+}
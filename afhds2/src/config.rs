@@ -0,0 +1,396 @@
+use a7105::{registers::*, A7105};
+
+use crate::RADIO_ID;
+
+/// Generates [`ConfigRegister`] along with the blocking and async helpers used to access it
+///
+/// Registers listed under `unverified` have a different meaning when read back, for example the
+/// RSSI threshold register reads back the measured RSSI, and so are skipped when verifying.
+macro_rules! config_registers {
+    (
+        verified { $($reg:ident),+ $(,)? }
+        unverified { $($wo_reg:ident),+ $(,)? }
+    ) => {
+        /// A single register value written while configuring the radio
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub enum ConfigRegister {
+            $($reg($reg),)+
+            $($wo_reg($wo_reg),)+
+        }
+
+        impl ConfigRegister {
+            #[cfg(feature = "blocking")]
+            pub(crate) fn write<SPI>(self, radio: &mut A7105<SPI>) -> Result<(), SPI::Error>
+            where
+                SPI: embedded_hal::spi::SpiDevice,
+            {
+                match self {
+                    $(Self::$reg(reg) => radio.write_reg(reg),)+
+                    $(Self::$wo_reg(reg) => radio.write_reg(reg),)+
+                }
+            }
+
+            #[cfg(feature = "async")]
+            pub(crate) async fn write<SPI>(self, radio: &mut A7105<SPI>) -> Result<(), SPI::Error>
+            where
+                SPI: embedded_hal_async::spi::SpiDevice,
+            {
+                match self {
+                    $(Self::$reg(reg) => radio.write_reg(reg).await,)+
+                    $(Self::$wo_reg(reg) => radio.write_reg(reg).await,)+
+                }
+            }
+
+            /// Read back the current value of this register, if it can be verified
+            #[cfg(feature = "blocking")]
+            pub(crate) fn read_back<SPI>(
+                self,
+                radio: &mut A7105<SPI>,
+            ) -> Result<Option<Self>, SPI::Error>
+            where
+                SPI: embedded_hal::spi::SpiDevice,
+            {
+                Ok(match self {
+                    $(Self::$reg(_) => Some(Self::$reg(radio.read_reg()?)),)+
+                    $(Self::$wo_reg(_) => None,)+
+                })
+            }
+
+            /// Read back the current value of this register, if it can be verified
+            #[cfg(feature = "async")]
+            pub(crate) async fn read_back<SPI>(
+                self,
+                radio: &mut A7105<SPI>,
+            ) -> Result<Option<Self>, SPI::Error>
+            where
+                SPI: embedded_hal_async::spi::SpiDevice,
+            {
+                Ok(match self {
+                    $(Self::$reg(_) => Some(Self::$reg(radio.read_reg().await?)),)+
+                    $(Self::$wo_reg(_) => None,)+
+                })
+            }
+        }
+    };
+}
+
+config_registers! {
+    verified {
+        Gpio1PinControl,
+        Gpio2PinControl,
+        IdData,
+        ModeControl,
+        Fifo1,
+        Fifo2,
+        RcOsc3,
+        Clock,
+        Pll1,
+        Pll5,
+        Delay2,
+        Rx,
+        RxGain1,
+        Code1,
+        Code2,
+        Code3,
+    }
+    unverified {
+        RssiCarrierDetectThreshold,
+        VcoCurrentCalibration,
+    }
+}
+
+/// The number of registers written while configuring the radio
+pub(crate) const REGISTER_COUNT: usize = 18;
+
+/// A register that did not read back the value written to it during configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfigMismatch {
+    /// The value written to the register
+    pub expected: ConfigRegister,
+    /// The value read back from the register
+    pub actual: ConfigRegister,
+}
+
+/// The mismatches found by [`verify_configuration`](crate::Afhds2::verify_configuration)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfigMismatches {
+    mismatches: [Option<ConfigMismatch>; REGISTER_COUNT],
+    len: usize,
+}
+
+impl ConfigMismatches {
+    pub(crate) const fn new() -> Self {
+        Self {
+            mismatches: [None; REGISTER_COUNT],
+            len: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, mismatch: ConfigMismatch) {
+        self.mismatches[self.len] = Some(mismatch);
+        self.len += 1;
+    }
+
+    /// Returns true if every register read back the value that was written to it
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of registers that did not read back the value that was written to them
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// An iterator over every mismatched register, in the order they were written
+    pub fn iter(&self) -> impl Iterator<Item = &ConfigMismatch> {
+        self.mismatches[..self.len].iter().flatten()
+    }
+}
+
+/// The tunable settings applied to the radio by
+/// [`configure_radio_with`](crate::Afhds2::configure_radio_with)
+///
+/// The [`Default`] configuration is the one used by [`configure_radio`](crate::Afhds2::configure_radio)
+/// and should only need adjusting to account for differences between board revisions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RadioConfig {
+    /// The ID used to identify packets on air
+    pub id: u32,
+    /// The RSSI level above which a carrier is considered detected
+    pub rssi_threshold: u8,
+    /// Enable forward error correction of packets
+    pub fec_enabled: bool,
+    /// Enable CRC checking of packets
+    pub crc_enabled: bool,
+    /// The length of the preamble sent before each packet
+    pub preamble_length: PreambleLength,
+    /// The key used to whiten packet data
+    pub encryption_key: u8,
+    /// The index of the last byte of the FIFO
+    pub fifo_end_pointer: u8,
+    /// The channel the PLL is tuned to once configured
+    pub channel: u8,
+    /// Use an external crystal rather than the internal oscillator
+    pub external_crystal_osc: bool,
+    /// The crystal reference counter used for clock generation
+    pub clock_generation_ref_cnt: u8,
+    /// The time allowed for the crystal to settle
+    pub xtal_settling_delay: XtalSettlingDelay,
+    /// The time allowed for the AGC to settle
+    pub agc_delay_settling: AgcDelaySettling,
+    /// The time allowed for an RSSI measurement
+    pub rssi_measurement_delay: RssiMeasurementDelay,
+}
+
+impl RadioConfig {
+    pub(crate) const DEFAULT: Self = Self {
+        id: RADIO_ID,
+        rssi_threshold: 0x32,
+        fec_enabled: true,
+        crc_enabled: true,
+        preamble_length: PreambleLength::Four,
+        // Packets are whitened with the same key by AFHDS2A transmitters
+        encryption_key: 0b1100_0011,
+        fifo_end_pointer: 0x25,
+        channel: 0x50,
+        external_crystal_osc: true,
+        clock_generation_ref_cnt: 0,
+        xtal_settling_delay: XtalSettlingDelay::Us200,
+        agc_delay_settling: AgcDelaySettling::Us10,
+        rssi_measurement_delay: RssiMeasurementDelay::Us10,
+    };
+
+    /// Create a [`RadioConfigBuilder`] starting from the default configuration
+    pub const fn builder() -> RadioConfigBuilder {
+        RadioConfigBuilder {
+            config: Self::DEFAULT,
+        }
+    }
+
+    /// The register values written after a reset, in the order they are written
+    ///
+    /// This is the single source of truth shared by the blocking and async implementations of
+    /// `configure_radio_with`.
+    pub(crate) fn registers(&self) -> [ConfigRegister; REGISTER_COUNT] {
+        [
+            // Configure GPIO1 as our MISO pin
+            ConfigRegister::Gpio1PinControl(Gpio1PinControl {
+                pin_function: GpioPinFunction::Sdo,
+                output_enabled: true,
+                ..Default::default()
+            }),
+            // Set the radio ID
+            ConfigRegister::IdData(IdData { id: self.id }),
+            // Mode Control
+            ConfigRegister::ModeControl(ModeControl {
+                auto_rssi: true,
+                data_mode: DataMode::FIFO,
+                ..Default::default()
+            }),
+            // FIFO Reg. 1
+            ConfigRegister::Fifo1(Fifo1 {
+                end_pointer: self.fifo_end_pointer,
+            }),
+            // FIFO Reg. 2
+            ConfigRegister::Fifo2(Fifo2 {
+                margin: 0,
+                segment: 0,
+            }),
+            // Configure GPIO2 as the WTR (wait to read) signal
+            ConfigRegister::Gpio2PinControl(Gpio2PinControl {
+                pin_function: GpioPinFunction::Wtr,
+                output_enabled: true,
+                ..Default::default()
+            }),
+            // RC OSC register 3
+            ConfigRegister::RcOsc3(RcOsc3 {
+                clock_select: ClockSelect::FSyncDiv8,
+            }),
+            // Clock Register
+            ConfigRegister::Clock(Clock {
+                external_crystal_osc: self.external_crystal_osc,
+                clock_generation_ref_cnt: self.clock_generation_ref_cnt,
+                ..Default::default()
+            }),
+            // PLL Register 1
+            ConfigRegister::Pll1(Pll1 {
+                channel: self.channel,
+            }),
+            // PLL Register 5
+            ConfigRegister::Pll5(Pll5 { bfp: 2 }),
+            // Delay Register 2
+            ConfigRegister::Delay2(Delay2 {
+                xtal_settling_delay: self.xtal_settling_delay,
+                agc_delay_settling: self.agc_delay_settling,
+                rssi_measurement_delay: self.rssi_measurement_delay,
+            }),
+            // Rx Register
+            ConfigRegister::Rx(Rx {
+                freq_compensation_enable: true,
+                ..Default::default()
+            }),
+            // Rx Gain Register 1
+            ConfigRegister::RxGain1(RxGain1 {
+                manual_vga_calibration: true,
+                ..Default::default()
+            }),
+            // RSSI Threshold
+            ConfigRegister::RssiCarrierDetectThreshold(RssiCarrierDetectThreshold {
+                threshold: self.rssi_threshold,
+            }),
+            // Code Register 1
+            ConfigRegister::Code1(Code1 {
+                fec_enabled: self.fec_enabled,
+                crc_enabled: self.crc_enabled,
+                id_length: IdLength::Four,
+                preable_length: self.preamble_length,
+                ..Default::default()
+            }),
+            // Code Register 2
+            ConfigRegister::Code2(Code2 {
+                demodulator_dc_estimation_average_mode: 1,
+                id_error_code_tolerance: IdErrorCodeTolerance::Bits0,
+                preamble_pattern_detection_length: PreabmelPatternDetectionLength::Bits16,
+                ..Default::default()
+            }),
+            // Code Register 3
+            ConfigRegister::Code3(Code3 {
+                encryption_key: self.encryption_key,
+            }),
+            // VCO current Calibration Register
+            ConfigRegister::VcoCurrentCalibration(VcoCurrentCalibration::Automatic),
+        ]
+    }
+}
+
+impl Default for RadioConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// A builder for a [`RadioConfig`], created by [`RadioConfig::builder`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RadioConfigBuilder {
+    config: RadioConfig,
+}
+
+impl RadioConfigBuilder {
+    /// Set the ID used to identify packets on air
+    pub const fn id(mut self, id: u32) -> Self {
+        self.config.id = id;
+        self
+    }
+
+    /// Set the RSSI level above which a carrier is considered detected
+    pub const fn rssi_threshold(mut self, threshold: u8) -> Self {
+        self.config.rssi_threshold = threshold;
+        self
+    }
+
+    /// Enable or disable forward error correction of packets
+    pub const fn fec_enabled(mut self, enabled: bool) -> Self {
+        self.config.fec_enabled = enabled;
+        self
+    }
+
+    /// Enable or disable CRC checking of packets
+    pub const fn crc_enabled(mut self, enabled: bool) -> Self {
+        self.config.crc_enabled = enabled;
+        self
+    }
+
+    /// Set the length of the preamble sent before each packet
+    pub const fn preamble_length(mut self, length: PreambleLength) -> Self {
+        self.config.preamble_length = length;
+        self
+    }
+
+    /// Set the key used to whiten packet data
+    pub const fn encryption_key(mut self, key: u8) -> Self {
+        self.config.encryption_key = key;
+        self
+    }
+
+    /// Set the index of the last byte of the FIFO
+    pub const fn fifo_end_pointer(mut self, end_pointer: u8) -> Self {
+        self.config.fifo_end_pointer = end_pointer;
+        self
+    }
+
+    /// Set the channel the PLL is tuned to once configured
+    pub const fn channel(mut self, channel: u8) -> Self {
+        self.config.channel = channel;
+        self
+    }
+
+    /// Set whether an external crystal is used, along with its reference counter
+    pub const fn crystal(mut self, external_crystal_osc: bool, ref_cnt: u8) -> Self {
+        self.config.external_crystal_osc = external_crystal_osc;
+        self.config.clock_generation_ref_cnt = ref_cnt;
+        self
+    }
+
+    /// Set the time allowed for the crystal to settle
+    pub const fn xtal_settling_delay(mut self, delay: XtalSettlingDelay) -> Self {
+        self.config.xtal_settling_delay = delay;
+        self
+    }
+
+    /// Set the time allowed for the AGC to settle
+    pub const fn agc_delay_settling(mut self, delay: AgcDelaySettling) -> Self {
+        self.config.agc_delay_settling = delay;
+        self
+    }
+
+    /// Set the time allowed for an RSSI measurement
+    pub const fn rssi_measurement_delay(mut self, delay: RssiMeasurementDelay) -> Self {
+        self.config.rssi_measurement_delay = delay;
+        self
+    }
+
+    /// Finish building the [`RadioConfig`]
+    pub const fn build(self) -> RadioConfig {
+        self.config
+    }
+}
@@ -0,0 +1,29 @@
+use crate::CalibrationError;
+
+/// The errors that can be reported by an [`Afhds2`](crate::Afhds2)
+///
+/// `SpiE` and `PinE` are the error types of the SPI device and GPIO pin the radio is using.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<SpiE, PinE> {
+    /// The underlying SPI bus reported an error
+    Spi(SpiE),
+    /// The GPIO pin connected to the radio reported an error
+    Pin(PinE),
+    /// The radio did not complete the requested operation in time
+    Timeout,
+    /// The radio failed to calibrate
+    Calibration(CalibrationError),
+    /// A packet was received with an invalid CRC
+    CrcError,
+    /// A packet was received with uncorrectable FEC errors
+    FecError,
+    /// A packet was received that is not one a transmitter sends
+    UnexpectedPacket,
+}
+
+impl<SpiE, PinE> From<CalibrationError> for Error<SpiE, PinE> {
+    fn from(err: CalibrationError) -> Self {
+        Self::Calibration(err)
+    }
+}
@@ -0,0 +1,63 @@
+use crate::{
+    packet::{FailsafePacket, NUM_CONTROL_CHANNELS},
+    Channels,
+};
+
+/// The failsafe positions configured on the transmitter
+///
+/// This is updated from every [`FailsafePacket`] received, and applied to the control channels
+/// once the link to the transmitter is lost. Channels without a failsafe position hold their last
+/// received value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Failsafe {
+    positions: [Option<u16>; NUM_CONTROL_CHANNELS],
+}
+
+impl Failsafe {
+    /// Create a new [`Failsafe`], channels set to `None` hold their last value
+    pub const fn new(positions: [Option<u16>; NUM_CONTROL_CHANNELS]) -> Self {
+        Self { positions }
+    }
+
+    /// Store the failsafe positions carried by the provided packet
+    pub fn update(&mut self, packet: &FailsafePacket) {
+        self.positions = *packet.failsafe();
+    }
+
+    /// The failsafe position of every channel
+    pub const fn positions(&self) -> &[Option<u16>; NUM_CONTROL_CHANNELS] {
+        &self.positions
+    }
+
+    /// The failsafe position of the channel at the provided index, if it is enabled
+    pub fn position(&self, index: usize) -> Option<u16> {
+        self.positions.get(index).copied().flatten()
+    }
+
+    /// Returns true if any channel has a failsafe position
+    pub fn is_configured(&self) -> bool {
+        self.positions.iter().any(Option::is_some)
+    }
+
+    /// Move every channel with a failsafe position to that position
+    ///
+    /// `channels` should hold the last values received, which are kept for any channel without a
+    /// failsafe position. Only the first 14 channels can have a failsafe position, limited to 12
+    /// bits when more than 14 channels are sent.
+    pub fn apply(&self, channels: &mut Channels) {
+        let mask = channels.count().value_mask();
+        for (index, position) in self.positions.iter().enumerate() {
+            if let Some(position) = position {
+                channels.set(index, position & mask);
+            }
+        }
+    }
+}
+
+impl From<&FailsafePacket> for Failsafe {
+    fn from(packet: &FailsafePacket) -> Self {
+        Self::new(*packet.failsafe())
+    }
+}
@@ -0,0 +1,43 @@
+/// The size in bytes of every AFHDS2A packet
+pub const PACKET_SIZE: usize = 37;
+
+/// An unparsed AFHDS2A packet, as read from or written to the radio FIFO
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RawFrame {
+    bytes: [u8; PACKET_SIZE],
+}
+
+impl RawFrame {
+    /// Create a new [`RawFrame`] from the provided bytes
+    pub const fn new(bytes: [u8; PACKET_SIZE]) -> Self {
+        Self { bytes }
+    }
+
+    /// The bytes of the packet
+    pub const fn as_bytes(&self) -> &[u8; PACKET_SIZE] {
+        &self.bytes
+    }
+
+    /// Mutable access to the bytes of the packet
+    pub fn as_bytes_mut(&mut self) -> &mut [u8; PACKET_SIZE] {
+        &mut self.bytes
+    }
+
+    /// The packet type, held in the first byte of every packet
+    pub const fn packet_type(&self) -> u8 {
+        self.bytes[0]
+    }
+}
+
+impl Default for RawFrame {
+    fn default() -> Self {
+        Self::new([0; PACKET_SIZE])
+    }
+}
+
+impl From<[u8; PACKET_SIZE]> for RawFrame {
+    fn from(bytes: [u8; PACKET_SIZE]) -> Self {
+        Self::new(bytes)
+    }
+}
@@ -0,0 +1,137 @@
+//! Tracking of the AFHDS2A frequency hopping sequence
+//!
+//! Once bound, a transmitter sends a single packet on each channel of its hop table in turn,
+//! moving to the next channel every [`HOP_PERIOD_US`]. The [`HopTracker`] follows this cadence so
+//! that the receiver can be tuned to the next channel before the transmitter gets there.
+
+use crate::{packet::NUM_HOP_CHANNELS, time::Instant};
+
+/// How often the transmitter moves to the next channel of the hop table
+pub const HOP_PERIOD_US: u32 = 3850;
+
+/// How long after a packet is expected to keep listening for it before hopping anyway
+pub const HOP_MARGIN_US: u32 = 500;
+
+/// How many packets in a row can be missed before sync is considered lost
+pub const MAX_MISSED_HOPS: u8 = NUM_HOP_CHANNELS as u8;
+
+/// How long to listen on a single channel while resyncing, long enough for the transmitter to
+/// pass through every channel of the hop table
+pub const RESYNC_DWELL_US: u32 = HOP_PERIOD_US * (NUM_HOP_CHANNELS as u32 + 1);
+
+/// Whether the tracker is following the transmitter or trying to find it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HopState {
+    /// Following the transmitter, expecting its next packet at `expected`
+    Synced {
+        /// When the next packet should finish arriving
+        expected: Instant,
+        /// How many packets in a row have been missed
+        missed: u8,
+    },
+    /// Sync was lost, parked on a single channel until `until` waiting for the transmitter to
+    /// come past
+    Resync {
+        /// When to give up on the current channel and move to the next one
+        until: Instant,
+    },
+}
+
+/// Follows the transmitter around its hop table
+///
+/// The tracker does not perform any IO itself. The driver listens on [`channel`](Self::channel)
+/// until [`deadline`](Self::deadline), reporting any packet received with
+/// [`packet_received`](Self::packet_received) and reporting the deadline passing with
+/// [`timeout`](Self::timeout). After either the tracker has moved to the channel the next packet
+/// will arrive on, which the radio can be tuned to straight away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HopTracker {
+    hop_table: [u8; NUM_HOP_CHANNELS],
+    index: usize,
+    state: HopState,
+}
+
+impl HopTracker {
+    /// Create a new [`HopTracker`] for the provided hop table, starting out resyncing at `now`
+    pub const fn new(hop_table: [u8; NUM_HOP_CHANNELS], now: Instant) -> Self {
+        Self {
+            hop_table,
+            index: 0,
+            state: HopState::Resync {
+                until: now.add_micros(RESYNC_DWELL_US),
+            },
+        }
+    }
+
+    /// Create a new [`HopTracker`] that starts out resyncing at `now` on the channel at the
+    /// provided position of the hop table
+    pub const fn with_index(hop_table: [u8; NUM_HOP_CHANNELS], index: usize, now: Instant) -> Self {
+        let mut tracker = Self::new(hop_table, now);
+        tracker.index = index % NUM_HOP_CHANNELS;
+        tracker
+    }
+
+    /// The hop table being followed
+    pub const fn hop_table(&self) -> &[u8; NUM_HOP_CHANNELS] {
+        &self.hop_table
+    }
+
+    /// The channel the next packet is expected on
+    pub const fn channel(&self) -> u8 {
+        self.hop_table[self.index]
+    }
+
+    /// The position of [`channel`](Self::channel) in the hop table
+    pub const fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The current [`HopState`]
+    pub const fn state(&self) -> HopState {
+        self.state
+    }
+
+    /// Returns true if the tracker is following the transmitter
+    pub const fn is_synced(&self) -> bool {
+        matches!(self.state, HopState::Synced { .. })
+    }
+
+    /// When to stop listening on the current channel and call [`timeout`](Self::timeout)
+    pub const fn deadline(&self) -> Instant {
+        match self.state {
+            HopState::Synced { expected, .. } => expected.add_micros(HOP_MARGIN_US),
+            HopState::Resync { until } => until,
+        }
+    }
+
+    /// Handle a packet from the transmitter finishing arriving on the current channel at `at`
+    pub fn packet_received(&mut self, at: Instant) {
+        self.advance();
+        self.state = HopState::Synced {
+            expected: at.add_micros(HOP_PERIOD_US),
+            missed: 0,
+        };
+    }
+
+    /// Handle the [`deadline`](Self::deadline) passing without a packet being received
+    pub fn timeout(&mut self, now: Instant) {
+        self.advance();
+        self.state = match self.state {
+            HopState::Synced { expected, missed } if missed + 1 < MAX_MISSED_HOPS => {
+                HopState::Synced {
+                    expected: expected.add_micros(HOP_PERIOD_US),
+                    missed: missed + 1,
+                }
+            }
+            _ => HopState::Resync {
+                until: now.add_micros(RESYNC_DWELL_US),
+            },
+        };
+    }
+
+    fn advance(&mut self) {
+        self.index = (self.index + 1) % NUM_HOP_CHANNELS;
+    }
+}
@@ -1,13 +1,106 @@
 #![no_std]
 
-use a7105::{commands::Command, registers::*, A7105};
+#[cfg(all(feature = "blocking", feature = "async"))]
+compile_error!("The `blocking` and `async` features are mutually exclusive");
+
+#[cfg(not(any(feature = "blocking", feature = "async")))]
+compile_error!("Either the `blocking` or `async` feature must be enabled");
+
+pub mod bind;
+mod calibration;
+mod channels;
+mod config;
+mod error;
+mod failsafe;
+mod frame;
+pub mod hopping;
+mod models;
+pub mod packet;
+mod protocol;
+mod receiver;
+mod receiver_id;
+mod rng;
+mod rssi;
+#[cfg(feature = "sim")]
+pub mod sim;
+mod spectrum;
+mod stats;
+mod status;
+pub mod store;
+pub mod telemetry;
+pub mod time;
+mod transmitter;
+
+use a7105::{
+    commands::{Command, Strobe},
+    registers::{self, *},
+    A7105,
+};
+#[cfg(feature = "async")]
+use embassy_futures::select::{select, Either};
+use packet::TransmitterPacket;
+
+pub use calibration::{
+    CalibrationError, CalibrationReport, CalibrationStage, VCO_CALIBRATION_CHANNELS,
+};
+pub use channels::{
+    ChannelCount, Channels, CENTER_US, MAX_CHANNELS, MAX_US, MIN_US, NORMALIZED_MAX,
+};
+pub use config::{
+    ConfigMismatch, ConfigMismatches, ConfigRegister, RadioConfig, RadioConfigBuilder,
+};
+pub use error::Error;
+pub use failsafe::Failsafe;
+pub use frame::{RawFrame, PACKET_SIZE};
+pub use models::{Model, ModelMemory, MAX_MODELS};
+pub use protocol::{Action, Event};
+pub use receiver::{Output, Receiver, FAILSAFE_TIMEOUT_US};
+pub use receiver_id::ReceiverId;
+pub use rssi::Rssi;
+pub use spectrum::{
+    ChannelOccupancy, SpectrumReport, BUSY_THRESHOLD_DBM, MAX_SCAN_CHANNEL, NUM_SCAN_CHANNELS,
+    SCAN_SAMPLES,
+};
+pub use stats::{LinkSnapshot, LinkStats};
+pub use status::RxStatus;
+pub use transmitter::{
+    Transmitter, TransmitterOutput, BIND_CONFIRMATIONS, MAX_HOP_CHANNEL, MIN_HOP_CHANNEL,
+};
+
+use protocol::Protocol;
+use time::{Clock, Instant};
 
 /// Magic ID for the a7105 for AFHDS2A flysky protocol
 pub const RADIO_ID: u32 = 0x5475C52A;
 
+/// How often the GPIO pin is polled while waiting for the WTR signal in blocking mode
+#[cfg(feature = "blocking")]
+const WTR_POLL_INTERVAL_US: u32 = 100;
+
+/// How long to wait for a packet to finish transmitting, well over the ~1ms it takes
+const TX_TIMEOUT_US: u32 = 5_000;
+
+/// The PLL channel to tune to in order to receive a packet sent on `channel`
+///
+/// AFHDS2A receivers listen one channel below the one the transmitter sends on.
+pub(crate) const fn rx_pll_channel(channel: u8) -> u8 {
+    channel.wrapping_sub(1)
+}
+
+/// The number of microseconds from now until `instant`, or zero if it has already passed
+fn micros_until<C: Clock>(instant: Instant, clock: &C) -> u32 {
+    instant.micros_since(clock.now()).min(u32::MAX as u64) as u32
+}
+
+/// An AFHDS2A radio built on an A7105 transceiver
+///
+/// The `gpio` pin must be connected to the GIO2 pin of the A7105, which
+/// [`configure_radio`](Self::configure_radio) sets up to output the WTR (wait to read) signal.
 pub struct Afhds2<SPI, P> {
     radio: A7105<SPI>,
     gpio: P,
+    config: RadioConfig,
+    channel: u8,
 }
 
 impl<SPI, P> Afhds2<SPI, P> {
@@ -18,6 +111,8 @@ impl<SPI, P> Afhds2<SPI, P> {
         Self {
             radio: A7105::new(spi),
             gpio,
+            config: RadioConfig::DEFAULT,
+            channel: RadioConfig::DEFAULT.channel,
         }
     }
 
@@ -26,7 +121,12 @@ impl<SPI, P> Afhds2<SPI, P> {
     /// This method is useful for cases where you may want to initialize the underlying hardware
     /// manually, for example to configure the PLL, crystal, or GPIO pins.
     pub const fn from_radio(radio: A7105<SPI>, gpio: P) -> Self {
-        Self { radio, gpio }
+        Self {
+            radio,
+            gpio,
+            config: RadioConfig::DEFAULT,
+            channel: RadioConfig::DEFAULT.channel,
+        }
     }
 }
 
@@ -43,654 +143,925 @@ where
     /// | Config   | Value   | Description |
     /// |----------|---------|-------------|
     /// | GPIO1    | MISO    | Configures the GPIO1 pin to act as the MISO pin |
+    /// | GPIO2    | WTR     | Configures the GPIO2 pin to signal when a packet is received |
     /// | Radio ID | [`RADIO_ID`] | Configures the Radio ID |
+    /// | RSSI threshold | 0x32 | Carrier detect threshold |
+    /// | Channel | 0x50 | The channel the PLL is initially tuned to |
     /// | Auto RSSI | True | Automatically perform RSSI measurement when entering RX mode
     /// | Data mode | FIFO | Use a FIFO for interfacing with the RX/TX data
-    pub fn configure_radio<D>(&mut self, mut delay: D) -> Result<(), SPI::Error>
+    ///
+    /// The radio should be calibrated with [`calibrate`](Self::calibrate) once configured.
+    ///
+    /// This is equivalent to [`configure_radio_with`](Self::configure_radio_with) using the
+    /// default [`RadioConfig`].
+    pub fn configure_radio<D>(&mut self, delay: D) -> Result<(), Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal::delay::DelayUs,
+    {
+        self.configure_radio_with(delay, &RadioConfig::default())
+    }
+
+    /// Configure the underlying radio hardware using the provided [`RadioConfig`]
+    ///
+    /// See [`configure_radio`](Self::configure_radio) for details of the configuration applied.
+    pub fn configure_radio_with<D>(
+        &mut self,
+        mut delay: D,
+        config: &RadioConfig,
+    ) -> Result<(), Error<SPI::Error, P::Error>>
     where
         D: embedded_hal::delay::DelayUs,
     {
         // Start by resetting the radio
-        self.radio.command(Command::Reset)?;
+        self.radio.command(Command::Reset).map_err(Error::Spi)?;
 
         // Give it time to actually perform the reset before continuing
         delay.delay_ms(50);
 
-        // Configure GPIO1 as our MISO pin
-        self.radio.write_reg(Gpio1PinControl {
-            pin_function: GpioPinFunction::Sdo,
-            output_enabled: true,
-            ..Default::default()
-        })?;
-
-        // Set the radio ID
-        self.radio.write_reg(IdData { id: RADIO_ID })?;
-
-        // Mode Control
-        self.radio.write_reg(ModeControl {
-            auto_rssi: true,
-            data_mode: DataMode::FIFO,
-            ..Default::default()
-        })?;
-
-        // FIFO Reg. 1
-        self.radio.write_reg(Fifo1 { end_pointer: 0x25 })?;
-
-        // FIFO Reg. 2
-        self.radio.write_reg(Fifo2 {
-            margin: 0,
-            segment: 0,
-        })?;
-
-        //     // GPIO2
-        //     debug!("Setting up GPIO 2 pin...");
-        //     // self.blocking_write_bytes(0xc, &[0b00_0001_01]);
-        //     self.blocking_write_bytes(0xc, &[0b00_0000_01]);
-
-        //     // RC OSC Register 1
-        //     debug!("Setting RC OSC Register 1");
-        //     self.blocking_write_bytes(0x7, &[0x00]);
-
-        //     // RC OSC Register 2
-        //     debug!("Setting RC OSC Register 2");
-        //     self.blocking_write_bytes(0x8, &[0x00]);
-
-        //     // Data Rate
-        //     debug!("Setting up Data Rate");
-        //     self.blocking_write_bytes(0xe, &[0x00]);
-
-        // RC OSC register 3
-        self.radio.write_reg(RcOsc3 {
-            clock_select: ClockSelect::FSyncDiv8,
-        })?;
-
-        // Clock Register
-        self.radio.write_reg(Clock {
-            external_crystal_osc: true,
-            clock_generation_ref_cnt: 0,
-            ..Default::default()
-        })?;
-
-        // PLL Register 1
-        self.radio.write_reg(Pll1 { channel: 0x50 })?;
-
-        // PLL Register 5
-        self.radio.write_reg(Pll5 { bfp: 2 })?;
-
-        // PLL Register 2
-        //     debug!("Setting PLL Register 2");
-        //     self.blocking_write_bytes(0x10, &[0x9e]);
-
-        //     // PLL Register 3
-        //     debug!("Setting PLL Register 3");
-        //     self.blocking_write_bytes(0x11, &[0x4b]);
-
-        //     // PLL Register 4
-        //     debug!("Setting PLL Register 4");
-        //     self.blocking_write_bytes(0x12, &[0x00]);
-
-        //     // TX Register 1
-        //     debug!("Setting TX Register 1");
-        //     self.blocking_write_bytes(0x14, &[0x16]);
-
-        // TODO: might have to set this?
-        //     // TX Register 2
-        //     debug!("Setting TX Register 2");
-        //     self.blocking_write_bytes(0x15, &[0x2b]);
-
-        //     // Delay Register 1
-        //     debug!("Setting Delay Register 1");
-        //     self.blocking_write_bytes(0x16, &[0x12]);
-
-        // Delay Register 2
-        self.radio.write_reg(Delay2 {
-            xtal_settling_delay: XtalSettlingDelay::Us200,
-            agc_delay_settling: AgcDelaySettling::Us10,
-            rssi_measurement_delay: RssiMeasurementDelay::Us10,
-        })?;
-
-        // Rx Register
-        self.radio.write_reg(Rx {
-            freq_compensation_enable: true,
-            ..Default::default()
-        })?;
-
-        // Rx Gain Register 1
-        self.radio.write_reg(RxGain1 {
-            manual_vga_calibration: true,
-            ..Default::default()
-        })?;
-
-        //     // Rx Gain Register 4
-        //     debug!("Setting Rx Gain Register");
-        //     self.blocking_write_bytes(0x1c, &[0x2a]);
-
-        // RSSI Threshold
+        self.config = *config;
+        self.channel = config.channel;
+        for reg in config.registers() {
+            reg.write(&mut self.radio).map_err(Error::Spi)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read back every register written by [`configure_radio_with`](Self::configure_radio_with)
+    /// and report any that do not hold the value that was written
+    ///
+    /// This catches bad SPI wiring or a brown-out during initialization, and should be performed
+    /// before [`calibrate`](Self::calibrate) as calibration retunes the PLL. Registers whose read
+    /// value has a different meaning than their written value, such as the RSSI threshold, are
+    /// not checked.
+    pub fn verify_configuration(
+        &mut self,
+    ) -> Result<ConfigMismatches, Error<SPI::Error, P::Error>> {
+        let mut mismatches = ConfigMismatches::new();
+
+        for expected in self.config.registers() {
+            if let Some(actual) = expected.read_back(&mut self.radio).map_err(Error::Spi)? {
+                if actual != expected {
+                    mismatches.push(ConfigMismatch { expected, actual });
+                }
+            }
+        }
+
+        Ok(mismatches)
+    }
+
+    /// Calibrate the IF filter bank, VCO current, and VCO bank of the radio
+    ///
+    /// This should be performed after [`configure_radio`](Self::configure_radio) as the chip
+    /// does not reliably receive packets until calibrated. The VCO bank is calibrated on each of
+    /// the [`VCO_CALIBRATION_CHANNELS`], leaving the PLL tuned to the last of them.
+    pub fn calibrate<D>(
+        &mut self,
+        mut delay: D,
+    ) -> Result<CalibrationReport, Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal::delay::DelayUs,
+    {
+        self.radio
+            .command(Command::Strobe(Strobe::Standby))
+            .map_err(Error::Spi)?;
+
+        // Calibrate IF filter bank
+        self.radio
+            .write_reg(CalibrationControl {
+                if_filter_bank: true,
+                ..Default::default()
+            })
+            .map_err(Error::Spi)?;
+        self.wait_calibration(&mut delay, CalibrationStage::IfFilterBank)?;
+        let if_filter_bank = self
+            .radio
+            .read_reg::<IfCalibrationResult>()
+            .map_err(Error::Spi)?;
+        if if_filter_bank.failed {
+            return Err(Error::Calibration(CalibrationError::Failed(
+                CalibrationStage::IfFilterBank,
+            )));
+        }
+
+        // Recomended calibration from A7105 Datasheet
         self.radio
-            .write_reg(RssiCarrierDetectThreshold { threshold: 0x32 })?;
-
-        // Code Register 1
-        self.radio.write_reg(Code1 {
-            fec_enabled: true,
-            crc_enabled: true,
-            id_length: IdLength::Four,
-            preable_length: PreambleLength::Four,
-            ..Default::default()
-        })?;
-
-        // Code Register 2
-        self.radio.write_reg(Code2 {
-            demodulator_dc_estimation_average_mode: 1,
-            id_error_code_tolerance: IdErrorCodeTolerance::Bits0,
-            preamble_pattern_detection_length: PreabmelPatternDetectionLength::Bits16,
-            ..Default::default()
-        })?;
-
-        // Code Register 3
-        // Why do we set the encryption key?!
-        self.radio.write_reg(Code3 {
-            encryption_key: 0b1100_0011,
-        })?;
-
-        //     // IF Calibration Register 1
-        //     debug!("Setting IF Calibration Register 1");
-        //     self.blocking_write_bytes(0x22, &[0x00]);
-
-        // VCO current Calibration Register
-        self.radio.write_reg(VcoCurrentCalibration::Automatic)?;
-
-        //     // VCO Single band Calibration Register 1
-        //     debug!("Setting VCO Single band Calibration Register 1");
-        //     self.blocking_write_bytes(0x25, &[0x00]);
-
-        //     // VCO Single band Calibration Register 2
-        //     debug!("Setting VCO Single band Calibration Register 2");
-        //     self.blocking_write_bytes(0x26, &[0x3b]);
-
-        //     // Battery detect Register
-        //     debug!("Setting Battery detect Register");
-        //     self.blocking_write_bytes(0x27, &[0x00]);
-
-        // BRODERICK: need to implement
-
-        //     // TX Test Register
-        //     debug!("Setting TX Test Register");
-        //     self.blocking_write_bytes(0x28, &[0x17]);
-
-        //     // Rx DEM test Register 1
-        //     debug!("Setting Rx DEM test Register 1");
-        //     self.blocking_write_bytes(0x29, &[0x47]);
-
-        //     // Rx DEM test Register 2
-        //     debug!("Setting Rx DEM test Register 2");
-        //     self.blocking_write_bytes(0x2a, &[0x80]);
-
-        //     // Charge Pump
-        //     debug!("Setting Charge Pump Current Register");
-        //     self.blocking_write_bytes(0x2b, &[0x03]);
-
-        //     // Crystal Test
-        //     debug!("Setting Crystal Test Register");
-        //     self.blocking_write_bytes(0x2c, &[0x01]);
-
-        //     // PLL Test
-        //     debug!("Setting PLL Test Register");
-        //     self.blocking_write_bytes(0x2d, &[0x45]);
-
-        //     // VCO Test 1
-        //     debug!("Setting VCO Test Register 1");
-        //     self.blocking_write_bytes(0x2e, &[0x18]);
-
-        //     // VCO Test 2
-        //     debug!("Setting VCO Test Register 2");
-        //     self.blocking_write_bytes(0x2f, &[0x00]);
-
-        //     // IFAT Register
-        //     debug!("Setting IFAT Register");
-        //     self.blocking_write_bytes(0x30, &[0x01]);
-
-        //     // RScale Register
-        //     debug!("Setting RScale Register");
-        //     self.blocking_write_bytes(0x31, &[0x0f]);
-
-        //     // Set to Standby mode
-        //     self.blocking_strobe(Strobe::Standby);
-
-        //     // Calibrate IF filter bank
-        //     debug!("IF filter bank calibration...");
-        //     self.blocking_write_bytes(0x02, &[0x01]);
-        //     self.blocking_wait_auto_clear(0x02, 0x01);
-
-        //     debug!("VCO Current Calibration");
-        //     //Recomended calibration from A7105 Datasheet
-        //     self.blocking_write_bytes(0x24, &[0x13]);
-
-        //     debug!("VCO bank calibration");
-        //     self.blocking_write_bytes(0x26, &[0x3b]);
-
-        //     debug!("VCO Bank Calibrate channel 0");
-        //     self.blocking_write_bytes(0x0f, &[0]); // set channel
-        //     self.blocking_write_bytes(0x02, &[0x02]);
-        //     self.blocking_wait_auto_clear(0x02, 0x02);
-
-        //     let cal0 = &mut [0];
-        //     self.blocking_read_register(0x25, cal0);
-        //     if (cal0[0] & 0x08) != 0 {
-        //         warn!("!!! VCO Calibration fail");
-        //     }
-
-        //     debug!("VCO Bank Calibrate channel 0xa0");
-        //     self.blocking_write_bytes(0x0f, &[0xa0]); // set channel
-        //     self.blocking_write_bytes(0x02, &[0x02]);
-        //     self.blocking_wait_auto_clear(0x02, 0x02);
-
-        //     let cal0 = &mut [0];
-        //     self.blocking_read_register(0x25, cal0);
-        //     debug!("vco cal a0={:02x}", cal0[0]);
-        //     if (cal0[0] & 0x08) != 0 {
-        //         debug!("!!! VCO Calibration fail");
-        //     }
-
-        //     debug!("Reset VCO band calibration");
-        //     self.blocking_write_bytes(0x25, &[0x08]);
-
-        //     debug!("Strobe standby");
-        //     self.blocking_strobe(Strobe::Standby);
-        //     self.register_dump();
-
-        //     let stored_channel = &mut [0];
-        //     self.blocking_read_register(0x0f, stored_channel);
-        //     if stored_channel[0] != 0xa0 {
-        //         warn!(
-        //             "Stored channel should have been 0xa0, was {}",
-        //             stored_channel[0]
-        //         );
-        //     }
-        //     debug!("End of radio_init");
-        // }
+            .write_reg(VcoCurrentCalibration::Manual(calibration::VCO_CURRENT))
+            .map_err(Error::Spi)?;
+        let vco_current = self
+            .radio
+            .read_reg::<VcoCurrentCalibrationResult>()
+            .map_err(Error::Spi)?;
 
+        self.radio
+            .write_reg(VcoSingleBandCalibration2 {
+                upper_threshold: calibration::VCO_UPPER_THRESHOLD,
+                lower_threshold: calibration::VCO_LOWER_THRESHOLD,
+            })
+            .map_err(Error::Spi)?;
+
+        let mut vco_bank = [0; 2];
+        for (bank, channel) in vco_bank.iter_mut().zip(VCO_CALIBRATION_CHANNELS) {
+            let stage = CalibrationStage::VcoBank { channel };
+
+            self.set_channel(channel)?;
+            self.radio
+                .write_reg(CalibrationControl {
+                    vco_bank: true,
+                    ..Default::default()
+                })
+                .map_err(Error::Spi)?;
+            self.wait_calibration(&mut delay, stage)?;
+
+            let result = self
+                .radio
+                .read_reg::<VcoBankCalibrationResult>()
+                .map_err(Error::Spi)?;
+            if result.failed {
+                return Err(Error::Calibration(CalibrationError::Failed(stage)));
+            }
+            *bank = result.value;
+        }
+
+        // Reset VCO band calibration
+        self.radio
+            .write_reg(VcoSingleBandCalibration1::Manual(0))
+            .map_err(Error::Spi)?;
+
+        self.radio
+            .command(Command::Strobe(Strobe::Standby))
+            .map_err(Error::Spi)?;
+
+        Ok(CalibrationReport {
+            if_filter_bank: if_filter_bank.value,
+            vco_current: vco_current.value,
+            vco_bank,
+        })
+    }
+
+    /// Wait for the radio to clear all of the calibration bits
+    fn wait_calibration<D>(
+        &mut self,
+        delay: &mut D,
+        stage: CalibrationStage,
+    ) -> Result<(), Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal::delay::DelayUs,
+    {
+        for _ in 0..calibration::POLL_ATTEMPTS {
+            let control = self
+                .radio
+                .read_reg::<CalibrationControl>()
+                .map_err(Error::Spi)?;
+            if !(control.if_filter_bank || control.vco_bank || control.vco_current) {
+                return Ok(());
+            }
+
+            delay.delay_us(calibration::POLL_INTERVAL_US);
+        }
+
+        Err(CalibrationError::Timeout(stage).into())
+    }
+
+    /// Tune the radio to the provided channel
+    pub fn set_channel(&mut self, channel: u8) -> Result<(), Error<SPI::Error, P::Error>> {
+        self.radio.write_reg(Pll1 { channel }).map_err(Error::Spi)?;
+        self.channel = channel;
         Ok(())
     }
+
+    /// Read the most recent RSSI measurement from the radio
+    ///
+    /// The radio only measures RSSI while in RX mode, so this reports the signal strength on the
+    /// currently tuned channel while receiving, or the strength of the last received packet.
+    pub fn rssi(&mut self) -> Result<Rssi, Error<SPI::Error, P::Error>> {
+        let rssi = self
+            .radio
+            .read_reg::<registers::Rssi>()
+            .map_err(Error::Spi)?;
+        Ok(Rssi::from_raw(rssi.value))
+    }
+
+    /// Measure the signal strength on every channel from 0 to [`MAX_SCAN_CHANNEL`]
+    ///
+    /// Each channel is listened on for `dwell_us` microseconds, with [`SCAN_SAMPLES`] RSSI samples
+    /// spread across the dwell. Any packet received during the dwell is discarded, though its
+    /// strength is still sampled. The radio is left in standby, tuned to the last channel.
+    pub fn spectrum_scan<D>(
+        &mut self,
+        mut delay: D,
+        dwell_us: u32,
+    ) -> Result<SpectrumReport, Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal::delay::DelayUs,
+    {
+        let mut report = SpectrumReport::new();
+        let interval_us = dwell_us / SCAN_SAMPLES;
+        for channel in 0..=MAX_SCAN_CHANNEL {
+            self.set_channel(channel)?;
+            self.radio
+                .command(Command::Strobe(Strobe::Rx))
+                .map_err(Error::Spi)?;
+
+            let mut samples = [Rssi::default(); SCAN_SAMPLES as usize];
+            for sample in &mut samples {
+                delay.delay_us(interval_us);
+                *sample = self.rssi()?;
+            }
+
+            self.radio
+                .command(Command::Strobe(Strobe::Standby))
+                .map_err(Error::Spi)?;
+            report.set(channel, ChannelOccupancy::from_samples(&samples));
+        }
+        Ok(report)
+    }
+
+    /// Receive a single packet on the currently tuned channel
+    ///
+    /// This places the radio in RX mode and waits up to `timeout_us` microseconds for the WTR
+    /// signal on the GPIO pin to indicate that a packet has been received. Packets that fail
+    /// their CRC or FEC checks are still returned, with the failure reported in the
+    /// [`RxStatus`], so that corrupted packets can be told apart from missed ones.
+    pub fn receive<D, C>(
+        &mut self,
+        mut delay: D,
+        clock: &C,
+        timeout_us: u32,
+    ) -> Result<(RawFrame, RxStatus), Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal::delay::DelayUs,
+        C: Clock,
+    {
+        self.radio
+            .command(Command::Strobe(Strobe::Rx))
+            .map_err(Error::Spi)?;
+        self.wait_wtr(&mut delay, timeout_us)?;
+
+        self.read_frame(clock.now())
+    }
+
+    /// Receive a single packet sent by a transmitter on the currently tuned channel
+    ///
+    /// Unlike [`receive`](Self::receive), packets that fail their CRC or FEC checks are reported
+    /// as [`Error::CrcError`] or [`Error::FecError`], and anything other than a packet a
+    /// transmitter sends as [`Error::UnexpectedPacket`].
+    pub fn receive_packet<D, C>(
+        &mut self,
+        delay: D,
+        clock: &C,
+        timeout_us: u32,
+    ) -> Result<(TransmitterPacket, RxStatus), Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal::delay::DelayUs,
+        C: Clock,
+    {
+        let (frame, status) = self.receive(delay, clock, timeout_us)?;
+        status.check()?;
+        let packet = TransmitterPacket::from_frame(&frame).map_err(|_| Error::UnexpectedPacket)?;
+        Ok((packet, status))
+    }
+
+    /// Transmit a single packet on the currently tuned channel
+    ///
+    /// This loads the packet into the FIFO, places the radio in TX mode and waits up to
+    /// `timeout_us` microseconds for the WTR signal to indicate that the packet has been sent.
+    pub fn transmit<D>(
+        &mut self,
+        mut delay: D,
+        frame: &RawFrame,
+        timeout_us: u32,
+    ) -> Result<(), Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal::delay::DelayUs,
+    {
+        self.radio
+            .command(Command::Strobe(Strobe::FifoWritePointerReset))
+            .map_err(Error::Spi)?;
+        self.radio.tx(frame.as_bytes()).map_err(Error::Spi)?;
+        self.radio
+            .command(Command::Strobe(Strobe::Tx))
+            .map_err(Error::Spi)?;
+        self.wait_wtr(&mut delay, timeout_us)
+    }
+
+    /// Run the provided [`Receiver`] until it has an [`Output`] for the application
+    ///
+    /// This performs each [`Action`] queued by the receiver in turn, reporting the outcome of every
+    /// receive back to it, and returns as soon as an output is delivered.
+    pub fn poll_receiver<D, C>(
+        &mut self,
+        receiver: &mut Receiver,
+        delay: D,
+        clock: &C,
+    ) -> Result<Output, Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal::delay::DelayUs,
+        C: Clock,
+    {
+        self.run(receiver, delay, clock)
+    }
+
+    /// Run the provided [`Transmitter`] until it has a [`TransmitterOutput`] for the application
+    ///
+    /// This performs each [`Action`] queued by the transmitter in turn, reporting the outcome of
+    /// every receive and wait back to it, and returns as soon as an output is delivered.
+    pub fn poll_transmitter<D, C>(
+        &mut self,
+        transmitter: &mut Transmitter,
+        delay: D,
+        clock: &C,
+    ) -> Result<TransmitterOutput, Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal::delay::DelayUs,
+        C: Clock,
+    {
+        self.run(transmitter, delay, clock)
+    }
+
+    /// Perform the actions queued by a protocol until it has an output for the application
+    fn run<Pr, D, C>(
+        &mut self,
+        protocol: &mut Pr,
+        mut delay: D,
+        clock: &C,
+    ) -> Result<Pr::Output, Error<SPI::Error, P::Error>>
+    where
+        Pr: Protocol,
+        D: embedded_hal::delay::DelayUs,
+        C: Clock,
+    {
+        loop {
+            match protocol.poll_action() {
+                Some(Action::Tune(channel)) => self.set_channel(channel)?,
+                Some(Action::Transmit(frame)) => {
+                    self.transmit(&mut delay, &frame, TX_TIMEOUT_US)?
+                }
+                Some(Action::Receive { deadline }) => {
+                    let timeout_us = micros_until(deadline, clock);
+                    match self.receive(&mut delay, clock, timeout_us) {
+                        Ok((frame, status)) => protocol.handle_event(Event::FrameReceived {
+                            frame: &frame,
+                            status,
+                        }),
+                        Err(Error::Timeout) => {
+                            protocol.handle_event(Event::TimerExpired { now: clock.now() })
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                Some(Action::Wait { until }) => {
+                    delay.delay_us(micros_until(until, clock));
+                    protocol.handle_event(Event::TimerExpired { now: clock.now() })
+                }
+                Some(Action::Deliver(output)) => return Ok(output),
+                // A previous error interrupted a receive, treat it as having timed out
+                None => protocol.handle_event(Event::TimerExpired { now: clock.now() }),
+            }
+        }
+    }
+
+    /// Wait for the WTR signal on the GPIO pin to indicate that a receive or transmit completed
+    ///
+    /// If it does not complete within `timeout_us` microseconds the radio is returned to standby.
+    fn wait_wtr<D>(
+        &mut self,
+        delay: &mut D,
+        timeout_us: u32,
+    ) -> Result<(), Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal::delay::DelayUs,
+    {
+        // WTR goes high once the radio enters RX or TX mode, and back low once it is done
+        let mut seen_high = false;
+        let mut elapsed = 0;
+        loop {
+            if self.gpio.is_high().map_err(Error::Pin)? {
+                seen_high = true;
+            } else if seen_high {
+                return Ok(());
+            }
+
+            if elapsed >= timeout_us {
+                self.radio
+                    .command(Command::Strobe(Strobe::Standby))
+                    .map_err(Error::Spi)?;
+                return Err(Error::Timeout);
+            }
+
+            delay.delay_us(WTR_POLL_INTERVAL_US);
+            elapsed = elapsed.saturating_add(WTR_POLL_INTERVAL_US);
+        }
+    }
+
+    /// Read the status of a completed receive and the packet out of the FIFO
+    fn read_frame(
+        &mut self,
+        timestamp: Instant,
+    ) -> Result<(RawFrame, RxStatus), Error<SPI::Error, P::Error>> {
+        let mode = self.radio.read_reg::<Mode>().map_err(Error::Spi)?;
+        let rssi = self
+            .radio
+            .read_reg::<registers::Rssi>()
+            .map_err(Error::Spi)?;
+        let status = RxStatus {
+            crc_error: mode.crc_error,
+            fec_error: mode.fec_error,
+            data_ready: !mode.trx_enabled,
+            timestamp,
+            channel: self.channel,
+            rssi: Rssi::from_raw(rssi.value),
+        };
+
+        let mut frame = RawFrame::default();
+        self.radio
+            .command(Command::Strobe(Strobe::FifoReadPointerReset))
+            .map_err(Error::Spi)?;
+        self.radio.rx(frame.as_bytes_mut()).map_err(Error::Spi)?;
+
+        Ok((frame, status))
+    }
 }
 
-// impl<'spi, 'cs, T, C, G, RD, TD> A7105<'spi, 'cs, T, C, G, RD, TD>
-// where
-//     T: Instance,
-//     C: Pin,
-//     G: Pin,
-//     RD: RxDma<T>,
-//     TD: TxDma<T>,
-// {
-//     pub fn new<Sck, Mosi, Miso, Cs, Gp, TxDma, RxDma>(
-//         peripheral: T,
-//         sck: Sck,
-//         mosi: Mosi,
-//         miso: Miso,
-//         cs: Cs,
-//         tx_dma: TxDma,
-//         rx_dma: RxDma,
-//         gpio1: Gp,
-//     ) -> Self
-//     where
-//         Sck: Peripheral + 'spi,
-//         <Sck as Peripheral>::P: SckPin<T>,
-//         Mosi: Peripheral + 'spi,
-//         <Mosi as Peripheral>::P: MosiPin<T>,
-//         Miso: Peripheral + 'spi,
-//         <Miso as Peripheral>::P: MisoPin<T>,
-//         Cs: Peripheral<P = C> + 'cs,
-//         Gp: Peripheral<P = G> + 'cs,
-//         TxDma: Peripheral<P = TD> + 'spi,
-//         RxDma: Peripheral<P = RD> + 'spi,
-//     {
-//         let mut spi_config = Config::default();
-//         // spi_config.frequency = Hertz(10_000_000);
-
-//         Self {
-//             spi: Spi::new(peripheral, sck, mosi, miso, tx_dma, rx_dma, spi_config),
-//             cs: Output::new(cs, Level::High, Speed::VeryHigh),
-//             gpio1: Input::new(gpio1, Pull::None),
-//         }
-//     }
-
-// pub async fn init(&mut self) {
-//     // Wait for it to be ready, in case we just powered on.
-//     Timer::after(Duration::from_millis(25)).await;
-
-//     // Zero - reset register.
-//     debug!("Resetting the a7105");
-//     self.blocking_write_bytes(0x0, &[0x00]);
-//     Timer::after(Duration::from_millis(50)).await;
-
-//     // Configure GPIO1 as our MISO pin
-//     debug!("Configuring GPIO 1 Pin...");
-//     self.blocking_write_bytes(0xB, &[0b00_0110_01]);
-
-//     // Write the Radio ID
-//     debug!("Setting Radio ID to {:?}", RADIO_ID);
-//     self.blocking_write_bytes(0x6, RADIO_ID);
-
-//     // Mode Control
-//     debug!("Setting Mode Control");
-//     self.blocking_write_bytes(0x1, &[0b0100_0010]);
-
-//     // Calibration Control
-//     debug!("Setting Calibration Control");
-//     self.blocking_write_bytes(0x2, &[0]);
-
-//     // FIFO Reg. 1
-//     debug!("Setting FIFO Reg. 1");
-//     self.blocking_write_bytes(0x3, &[0x25]);
-
-//     // FIFO Reg. 2
-//     debug!("Setting FIFO Reg. 2");
-//     self.blocking_write_bytes(0x4, &[0x00]);
-
-//     // RC OSC Register 1
-//     debug!("Setting RC OSC Register 1");
-//     self.blocking_write_bytes(0x7, &[0x00]);
-
-//     // RC OSC Register 2
-//     debug!("Setting RC OSC Register 2");
-//     self.blocking_write_bytes(0x8, &[0x00]);
-
-//     // RC OSC Register 3
-//     debug!("Setting RC OSC Register 3");
-//     self.blocking_write_bytes(0x9, &[0x00]);
-
-//     // GPIO2
-//     debug!("Setting up GPIO 2 pin...");
-//     // self.blocking_write_bytes(0xc, &[0b00_0001_01]);
-//     self.blocking_write_bytes(0xc, &[0b00_0000_01]);
-
-//     // Clock Register
-//     debug!("Setting up Clock");
-//     self.blocking_write_bytes(0xd, &[0x5]);
-
-//     // Data Rate
-//     debug!("Setting up Data Rate");
-//     self.blocking_write_bytes(0xe, &[0x00]);
-
-//     // PLL Register 1
-//     debug!("Setting PLL Register 1");
-//     self.blocking_write_bytes(0xf, &[0x50]);
-
-//     // PLL Register 2
-//     debug!("Setting PLL Register 2");
-//     self.blocking_write_bytes(0x10, &[0x9e]);
-
-//     // PLL Register 3
-//     debug!("Setting PLL Register 3");
-//     self.blocking_write_bytes(0x11, &[0x4b]);
-
-//     // PLL Register 4
-//     debug!("Setting PLL Register 4");
-//     self.blocking_write_bytes(0x12, &[0x00]);
-
-//     // PLL Register 5
-//     debug!("Setting PLL Register 5");
-//     self.blocking_write_bytes(0x13, &[0x02]);
-
-//     // TX Register 1
-//     debug!("Setting TX Register 1");
-//     self.blocking_write_bytes(0x14, &[0x16]);
-
-//     // TX Register 2
-//     debug!("Setting TX Register 2");
-//     self.blocking_write_bytes(0x15, &[0x2b]);
-
-//     // Delay Register 1
-//     debug!("Setting Delay Register 1");
-//     self.blocking_write_bytes(0x16, &[0x12]);
-
-//     // Delay Register 2
-//     debug!("Setting Delay Register 2");
-//     self.blocking_write_bytes(0x17, &[0x00]);
-
-//     // Rx Register
-//     debug!("Setting Rx Register");
-//     self.blocking_write_bytes(0x18, &[0x62]);
+#[cfg(feature = "async")]
+impl<SPI, P> Afhds2<SPI, P>
+where
+    SPI: embedded_hal_async::spi::SpiDevice,
+    P: embedded_hal_async::digital::Wait,
+{
+    /// A function to configure the underlying radio hardware
+    ///
+    /// This method will reset the radio to its defaults, with the following modifications:
+    ///
+    /// | Config   | Value   | Description |
+    /// |----------|---------|-------------|
+    /// | GPIO1    | MISO    | Configures the GPIO1 pin to act as the MISO pin |
+    /// | GPIO2    | WTR     | Configures the GPIO2 pin to signal when a packet is received |
+    /// | Radio ID | [`RADIO_ID`] | Configures the Radio ID |
+    /// | RSSI threshold | 0x32 | Carrier detect threshold |
+    /// | Channel | 0x50 | The channel the PLL is initially tuned to |
+    /// | Auto RSSI | True | Automatically perform RSSI measurement when entering RX mode
+    /// | Data mode | FIFO | Use a FIFO for interfacing with the RX/TX data
+    ///
+    /// The radio should be calibrated with [`calibrate`](Self::calibrate) once configured.
+    ///
+    /// This is equivalent to [`configure_radio_with`](Self::configure_radio_with) using the
+    /// default [`RadioConfig`].
+    pub async fn configure_radio<D>(&mut self, delay: D) -> Result<(), Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal_async::delay::DelayUs,
+    {
+        self.configure_radio_with(delay, &RadioConfig::default())
+            .await
+    }
+
+    /// Configure the underlying radio hardware using the provided [`RadioConfig`]
+    ///
+    /// See [`configure_radio`](Self::configure_radio) for details of the configuration applied.
+    pub async fn configure_radio_with<D>(
+        &mut self,
+        mut delay: D,
+        config: &RadioConfig,
+    ) -> Result<(), Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal_async::delay::DelayUs,
+    {
+        // Start by resetting the radio
+        self.radio
+            .command(Command::Reset)
+            .await
+            .map_err(Error::Spi)?;
+
+        // Give it time to actually perform the reset before continuing
+        delay.delay_ms(50).await;
+
+        self.config = *config;
+        self.channel = config.channel;
+        for reg in config.registers() {
+            reg.write(&mut self.radio).await.map_err(Error::Spi)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read back every register written by [`configure_radio_with`](Self::configure_radio_with)
+    /// and report any that do not hold the value that was written
+    ///
+    /// This catches bad SPI wiring or a brown-out during initialization, and should be performed
+    /// before [`calibrate`](Self::calibrate) as calibration retunes the PLL. Registers whose read
+    /// value has a different meaning than their written value, such as the RSSI threshold, are
+    /// not checked.
+    pub async fn verify_configuration(
+        &mut self,
+    ) -> Result<ConfigMismatches, Error<SPI::Error, P::Error>> {
+        let mut mismatches = ConfigMismatches::new();
+
+        for expected in self.config.registers() {
+            if let Some(actual) = expected
+                .read_back(&mut self.radio)
+                .await
+                .map_err(Error::Spi)?
+            {
+                if actual != expected {
+                    mismatches.push(ConfigMismatch { expected, actual });
+                }
+            }
+        }
+
+        Ok(mismatches)
+    }
+
+    /// Calibrate the IF filter bank, VCO current, and VCO bank of the radio
+    ///
+    /// This should be performed after [`configure_radio`](Self::configure_radio) as the chip
+    /// does not reliably receive packets until calibrated. The VCO bank is calibrated on each of
+    /// the [`VCO_CALIBRATION_CHANNELS`], leaving the PLL tuned to the last of them.
+    pub async fn calibrate<D>(
+        &mut self,
+        mut delay: D,
+    ) -> Result<CalibrationReport, Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal_async::delay::DelayUs,
+    {
+        self.radio
+            .command(Command::Strobe(Strobe::Standby))
+            .await
+            .map_err(Error::Spi)?;
+
+        // Calibrate IF filter bank
+        self.radio
+            .write_reg(CalibrationControl {
+                if_filter_bank: true,
+                ..Default::default()
+            })
+            .await
+            .map_err(Error::Spi)?;
+        self.wait_calibration(&mut delay, CalibrationStage::IfFilterBank)
+            .await?;
+        let if_filter_bank = self
+            .radio
+            .read_reg::<IfCalibrationResult>()
+            .await
+            .map_err(Error::Spi)?;
+        if if_filter_bank.failed {
+            return Err(Error::Calibration(CalibrationError::Failed(
+                CalibrationStage::IfFilterBank,
+            )));
+        }
+
+        // Recomended calibration from A7105 Datasheet
+        self.radio
+            .write_reg(VcoCurrentCalibration::Manual(calibration::VCO_CURRENT))
+            .await
+            .map_err(Error::Spi)?;
+        let vco_current = self
+            .radio
+            .read_reg::<VcoCurrentCalibrationResult>()
+            .await
+            .map_err(Error::Spi)?;
+
+        self.radio
+            .write_reg(VcoSingleBandCalibration2 {
+                upper_threshold: calibration::VCO_UPPER_THRESHOLD,
+                lower_threshold: calibration::VCO_LOWER_THRESHOLD,
+            })
+            .await
+            .map_err(Error::Spi)?;
+
+        let mut vco_bank = [0; 2];
+        for (bank, channel) in vco_bank.iter_mut().zip(VCO_CALIBRATION_CHANNELS) {
+            let stage = CalibrationStage::VcoBank { channel };
+
+            self.set_channel(channel).await?;
+            self.radio
+                .write_reg(CalibrationControl {
+                    vco_bank: true,
+                    ..Default::default()
+                })
+                .await
+                .map_err(Error::Spi)?;
+            self.wait_calibration(&mut delay, stage).await?;
+
+            let result = self
+                .radio
+                .read_reg::<VcoBankCalibrationResult>()
+                .await
+                .map_err(Error::Spi)?;
+            if result.failed {
+                return Err(Error::Calibration(CalibrationError::Failed(stage)));
+            }
+            *bank = result.value;
+        }
+
+        // Reset VCO band calibration
+        self.radio
+            .write_reg(VcoSingleBandCalibration1::Manual(0))
+            .await
+            .map_err(Error::Spi)?;
+
+        self.radio
+            .command(Command::Strobe(Strobe::Standby))
+            .await
+            .map_err(Error::Spi)?;
+
+        Ok(CalibrationReport {
+            if_filter_bank: if_filter_bank.value,
+            vco_current: vco_current.value,
+            vco_bank,
+        })
+    }
+
+    /// Wait for the radio to clear all of the calibration bits
+    async fn wait_calibration<D>(
+        &mut self,
+        delay: &mut D,
+        stage: CalibrationStage,
+    ) -> Result<(), Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal_async::delay::DelayUs,
+    {
+        for _ in 0..calibration::POLL_ATTEMPTS {
+            let control = self
+                .radio
+                .read_reg::<CalibrationControl>()
+                .await
+                .map_err(Error::Spi)?;
+            if !(control.if_filter_bank || control.vco_bank || control.vco_current) {
+                return Ok(());
+            }
+
+            delay.delay_us(calibration::POLL_INTERVAL_US).await;
+        }
+
+        Err(CalibrationError::Timeout(stage).into())
+    }
 
-//     // Rx Gain Register 1
-//     debug!("Setting Rx Gain Register");
-//     self.blocking_write_bytes(0x19, &[0x80]);
+    /// Tune the radio to the provided channel
+    pub async fn set_channel(&mut self, channel: u8) -> Result<(), Error<SPI::Error, P::Error>> {
+        self.radio
+            .write_reg(Pll1 { channel })
+            .await
+            .map_err(Error::Spi)?;
+        self.channel = channel;
+        Ok(())
+    }
 
-//     // Rx Gain Register 4
-//     debug!("Setting Rx Gain Register");
-//     self.blocking_write_bytes(0x1c, &[0x2a]);
+    /// Read the most recent RSSI measurement from the radio
+    ///
+    /// The radio only measures RSSI while in RX mode, so this reports the signal strength on the
+    /// currently tuned channel while receiving, or the strength of the last received packet.
+    pub async fn rssi(&mut self) -> Result<Rssi, Error<SPI::Error, P::Error>> {
+        let rssi = self
+            .radio
+            .read_reg::<registers::Rssi>()
+            .await
+            .map_err(Error::Spi)?;
+        Ok(Rssi::from_raw(rssi.value))
+    }
 
-//     // RSSI Threshold
-//     debug!("Setting RSSI Threshold Register");
-//     self.blocking_write_bytes(0x1d, &[0x32]);
-
-//     // ADC Control
-//     debug!("Setting ADC Control Register");
-//     self.blocking_write_bytes(0x1e, &[0xc3]);
-
-//     // Code Register 1
-//     debug!("Setting Code Register 1");
-//     self.blocking_write_bytes(0x1f, &[0b0001_1111]);
-
-//     // Code Register 2
-//     debug!("Setting Code Register 2");
-//     self.blocking_write_bytes(0x20, &[0x13]);
-
-//     // Code Register 3
-//     debug!("Setting Code Register 3");
-//     self.blocking_write_bytes(0x21, &[0xc3]);
-
-//     // IF Calibration Register 1
-//     debug!("Setting IF Calibration Register 1");
-//     self.blocking_write_bytes(0x22, &[0x00]);
-
-//     // VCO current Calibration Register
-//     debug!("Setting VCO current Calibration Register");
-//     self.blocking_write_bytes(0x24, &[0x00]);
-
-//     // VCO Single band Calibration Register 1
-//     debug!("Setting VCO Single band Calibration Register 1");
-//     self.blocking_write_bytes(0x25, &[0x00]);
-
-//     // VCO Single band Calibration Register 2
-//     debug!("Setting VCO Single band Calibration Register 2");
-//     self.blocking_write_bytes(0x26, &[0x3b]);
-
-//     // Battery detect Register
-//     debug!("Setting Battery detect Register");
-//     self.blocking_write_bytes(0x27, &[0x00]);
-
-//     // TX Test Register
-//     debug!("Setting TX Test Register");
-//     self.blocking_write_bytes(0x28, &[0x17]);
-
-//     // Rx DEM test Register 1
-//     debug!("Setting Rx DEM test Register 1");
-//     self.blocking_write_bytes(0x29, &[0x47]);
-
-//     // Rx DEM test Register 2
-//     debug!("Setting Rx DEM test Register 2");
-//     self.blocking_write_bytes(0x2a, &[0x80]);
-
-//     // Charge Pump
-//     debug!("Setting Charge Pump Current Register");
-//     self.blocking_write_bytes(0x2b, &[0x03]);
-
-//     // Crystal Test
-//     debug!("Setting Crystal Test Register");
-//     self.blocking_write_bytes(0x2c, &[0x01]);
-
-//     // PLL Test
-//     debug!("Setting PLL Test Register");
-//     self.blocking_write_bytes(0x2d, &[0x45]);
-
-//     // VCO Test 1
-//     debug!("Setting VCO Test Register 1");
-//     self.blocking_write_bytes(0x2e, &[0x18]);
-
-//     // VCO Test 2
-//     debug!("Setting VCO Test Register 2");
-//     self.blocking_write_bytes(0x2f, &[0x00]);
-
-//     // IFAT Register
-//     debug!("Setting IFAT Register");
-//     self.blocking_write_bytes(0x30, &[0x01]);
-
-//     // RScale Register
-//     debug!("Setting RScale Register");
-//     self.blocking_write_bytes(0x31, &[0x0f]);
-
-//     // Set to Standby mode
-//     self.blocking_strobe(Strobe::Standby);
-
-//     // Calibrate IF filter bank
-//     debug!("IF filter bank calibration...");
-//     self.blocking_write_bytes(0x02, &[0x01]);
-//     self.blocking_wait_auto_clear(0x02, 0x01);
-
-//     debug!("VCO Current Calibration");
-//     //Recomended calibration from A7105 Datasheet
-//     self.blocking_write_bytes(0x24, &[0x13]);
-
-//     debug!("VCO bank calibration");
-//     self.blocking_write_bytes(0x26, &[0x3b]);
-
-//     debug!("VCO Bank Calibrate channel 0");
-//     self.blocking_write_bytes(0x0f, &[0]); // set channel
-//     self.blocking_write_bytes(0x02, &[0x02]);
-//     self.blocking_wait_auto_clear(0x02, 0x02);
-
-//     let cal0 = &mut [0];
-//     self.blocking_read_register(0x25, cal0);
-//     if (cal0[0] & 0x08) != 0 {
-//         warn!("!!! VCO Calibration fail");
-//     }
-
-//     debug!("VCO Bank Calibrate channel 0xa0");
-//     self.blocking_write_bytes(0x0f, &[0xa0]); // set channel
-//     self.blocking_write_bytes(0x02, &[0x02]);
-//     self.blocking_wait_auto_clear(0x02, 0x02);
-
-//     let cal0 = &mut [0];
-//     self.blocking_read_register(0x25, cal0);
-//     debug!("vco cal a0={:02x}", cal0[0]);
-//     if (cal0[0] & 0x08) != 0 {
-//         debug!("!!! VCO Calibration fail");
-//     }
-
-//     debug!("Reset VCO band calibration");
-//     self.blocking_write_bytes(0x25, &[0x08]);
-
-//     debug!("Strobe standby");
-//     self.blocking_strobe(Strobe::Standby);
-//     self.register_dump();
-
-//     let stored_channel = &mut [0];
-//     self.blocking_read_register(0x0f, stored_channel);
-//     if stored_channel[0] != 0xa0 {
-//         warn!(
-//             "Stored channel should have been 0xa0, was {}",
-//             stored_channel[0]
-//         );
-//     }
-//     debug!("End of radio_init");
-// }
-
-// pub fn set_channel(&mut self, chn: u8) {
-//     self.blocking_write_bytes(0x0f, &[chn]);
-// }
-
-// pub async fn wait_gpio(&mut self) -> bool {
-//     let mut cnt = 0;
-//     // Wait for it to go high
-//     while self.gpio1.is_low() {
-//         Timer::after(Duration::from_micros(100)).await;
-//         cnt += 1;
-
-//         if cnt > 2000 {
-//             return false;
-//         }
-//     }
-
-//     let mut cnt = 0;
-//     // Wait for it to go low
-//     while self.gpio1.is_high() {
-//         Timer::after(Duration::from_micros(1)).await;
-//         cnt += 1;
-
-//         if cnt > 2000 {
-//             return false;
-//         }
-//     }
-
-//     true
-// }
-
-// fn register_dump(&mut self) {
-//     let id = &mut [0, 0, 0, 0];
-//     self.blocking_read_register(0x06, id);
-//     debug!("Dumped ID: {:?}", id);
-// }
-
-// pub fn blocking_strobe(&mut self, cmd: Strobe) {
-//     let byte: u8 = cmd.into();
-
-//     self.cs.set_low();
-//     self.spi.blocking_write(&[byte]).ok();
-//     self.cs.set_high();
-// }
-
-// pub fn blocking_write_bytes(&mut self, mut addr: u8, bytes: &[u8]) {
-//     // Mask off the bits indicating it is a write
-//     addr &= 0x3f;
-
-//     self.cs.set_low();
-//     self.spi.blocking_write(&[addr]).ok();
-//     self.spi.blocking_write(bytes).ok();
-//     self.cs.set_high();
-// }
-
-// pub fn blocking_read_register(&mut self, mut addr: u8, bytes: &mut [u8]) {
-//     // Mask off the bits indicating it is a read
-//     addr = (addr & 0x3f) | 0x40;
-
-//     self.cs.set_low();
-//     self.spi.blocking_write(&[addr]).ok();
-//     self.spi.blocking_read(bytes).ok();
-//     self.cs.set_high();
-// }
-
-// pub fn blocking_wait_auto_clear(&mut self, addr: u8, bit: u8) {
-//     let byte = &mut [0];
-//     loop {
-//         self.blocking_read_register(addr, byte);
-//         if (byte[0] & bit) == 0 {
-//             break;
-//         }
-//     }
-// }
-
-// pub fn blocking_strobe_then_read(&mut self, cmd: Strobe, mut addr: u8, bytes: &mut [u8]) {
-//     let byte: u8 = cmd.into();
-
-//     // Mask off the bits indicating it is a read
-//     addr = (addr & 0x3f) | 0x40;
-
-//     self.cs.set_low();
-//     self.spi.blocking_write(&[byte, addr]).ok();
-//     self.spi.blocking_read(bytes).ok();
-//     self.cs.set_high();
-// }
-
-// pub fn blocking_write_then_strobe(&mut self, cmd: Strobe, mut addr: u8, bytes: &[u8]) {
-//     let byte: u8 = cmd.into();
-
-//     // Mask off the bits indicating it is a read
-//     addr &= 0x3f;
-
-//     self.cs.set_low();
-//     self.spi.blocking_write(&[addr]).ok();
-//     self.spi.blocking_write(bytes).ok();
-//     self.spi.blocking_write(&[byte]).ok();
-//     self.cs.set_high();
-// }
-// }
+    /// Measure the signal strength on every channel from 0 to [`MAX_SCAN_CHANNEL`]
+    ///
+    /// Each channel is listened on for `dwell_us` microseconds, with [`SCAN_SAMPLES`] RSSI samples
+    /// spread across the dwell. Any packet received during the dwell is discarded, though its
+    /// strength is still sampled. The radio is left in standby, tuned to the last channel.
+    pub async fn spectrum_scan<D>(
+        &mut self,
+        mut delay: D,
+        dwell_us: u32,
+    ) -> Result<SpectrumReport, Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal_async::delay::DelayUs,
+    {
+        let mut report = SpectrumReport::new();
+        let interval_us = dwell_us / SCAN_SAMPLES;
+        for channel in 0..=MAX_SCAN_CHANNEL {
+            self.set_channel(channel).await?;
+            self.radio
+                .command(Command::Strobe(Strobe::Rx))
+                .await
+                .map_err(Error::Spi)?;
+
+            let mut samples = [Rssi::default(); SCAN_SAMPLES as usize];
+            for sample in &mut samples {
+                delay.delay_us(interval_us).await;
+                *sample = self.rssi().await?;
+            }
+
+            self.radio
+                .command(Command::Strobe(Strobe::Standby))
+                .await
+                .map_err(Error::Spi)?;
+            report.set(channel, ChannelOccupancy::from_samples(&samples));
+        }
+        Ok(report)
+    }
+
+    /// Receive a single packet on the currently tuned channel
+    ///
+    /// This places the radio in RX mode and waits up to `timeout_us` microseconds for the falling
+    /// edge of the WTR signal on the GPIO pin, which indicates that a packet has been received.
+    /// Packets that fail their CRC or FEC checks are still returned, with the failure reported in
+    /// the [`RxStatus`], so that corrupted packets can be told apart from missed ones.
+    pub async fn receive<D, C>(
+        &mut self,
+        mut delay: D,
+        clock: &C,
+        timeout_us: u32,
+    ) -> Result<(RawFrame, RxStatus), Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal_async::delay::DelayUs,
+        C: Clock,
+    {
+        self.radio
+            .command(Command::Strobe(Strobe::Rx))
+            .await
+            .map_err(Error::Spi)?;
+        self.wait_wtr(&mut delay, timeout_us).await?;
+
+        self.read_frame(clock.now()).await
+    }
+
+    /// Receive a single packet sent by a transmitter on the currently tuned channel
+    ///
+    /// Unlike [`receive`](Self::receive), packets that fail their CRC or FEC checks are reported
+    /// as [`Error::CrcError`] or [`Error::FecError`], and anything other than a packet a
+    /// transmitter sends as [`Error::UnexpectedPacket`].
+    pub async fn receive_packet<D, C>(
+        &mut self,
+        delay: D,
+        clock: &C,
+        timeout_us: u32,
+    ) -> Result<(TransmitterPacket, RxStatus), Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal_async::delay::DelayUs,
+        C: Clock,
+    {
+        let (frame, status) = self.receive(delay, clock, timeout_us).await?;
+        status.check()?;
+        let packet = TransmitterPacket::from_frame(&frame).map_err(|_| Error::UnexpectedPacket)?;
+        Ok((packet, status))
+    }
+
+    /// Transmit a single packet on the currently tuned channel
+    ///
+    /// This loads the packet into the FIFO, places the radio in TX mode and waits up to
+    /// `timeout_us` microseconds for the WTR signal to indicate that the packet has been sent.
+    pub async fn transmit<D>(
+        &mut self,
+        mut delay: D,
+        frame: &RawFrame,
+        timeout_us: u32,
+    ) -> Result<(), Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal_async::delay::DelayUs,
+    {
+        self.radio
+            .command(Command::Strobe(Strobe::FifoWritePointerReset))
+            .await
+            .map_err(Error::Spi)?;
+        self.radio.tx(frame.as_bytes()).await.map_err(Error::Spi)?;
+        self.radio
+            .command(Command::Strobe(Strobe::Tx))
+            .await
+            .map_err(Error::Spi)?;
+        self.wait_wtr(&mut delay, timeout_us).await
+    }
+
+    /// Run the provided [`Receiver`] until it has an [`Output`] for the application
+    ///
+    /// This performs each [`Action`] queued by the receiver in turn, reporting the outcome of every
+    /// receive back to it, and returns as soon as an output is delivered.
+    pub async fn poll_receiver<D, C>(
+        &mut self,
+        receiver: &mut Receiver,
+        delay: D,
+        clock: &C,
+    ) -> Result<Output, Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal_async::delay::DelayUs,
+        C: Clock,
+    {
+        self.run(receiver, delay, clock).await
+    }
+
+    /// Run the provided [`Transmitter`] until it has a [`TransmitterOutput`] for the application
+    ///
+    /// This performs each [`Action`] queued by the transmitter in turn, reporting the outcome of
+    /// every receive and wait back to it, and returns as soon as an output is delivered.
+    pub async fn poll_transmitter<D, C>(
+        &mut self,
+        transmitter: &mut Transmitter,
+        delay: D,
+        clock: &C,
+    ) -> Result<TransmitterOutput, Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal_async::delay::DelayUs,
+        C: Clock,
+    {
+        self.run(transmitter, delay, clock).await
+    }
+
+    /// Perform the actions queued by a protocol until it has an output for the application
+    async fn run<Pr, D, C>(
+        &mut self,
+        protocol: &mut Pr,
+        mut delay: D,
+        clock: &C,
+    ) -> Result<Pr::Output, Error<SPI::Error, P::Error>>
+    where
+        Pr: Protocol,
+        D: embedded_hal_async::delay::DelayUs,
+        C: Clock,
+    {
+        loop {
+            match protocol.poll_action() {
+                Some(Action::Tune(channel)) => self.set_channel(channel).await?,
+                Some(Action::Transmit(frame)) => {
+                    self.transmit(&mut delay, &frame, TX_TIMEOUT_US).await?
+                }
+                Some(Action::Receive { deadline }) => {
+                    let timeout_us = micros_until(deadline, clock);
+                    match self.receive(&mut delay, clock, timeout_us).await {
+                        Ok((frame, status)) => protocol.handle_event(Event::FrameReceived {
+                            frame: &frame,
+                            status,
+                        }),
+                        Err(Error::Timeout) => {
+                            protocol.handle_event(Event::TimerExpired { now: clock.now() })
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                Some(Action::Wait { until }) => {
+                    delay.delay_us(micros_until(until, clock)).await;
+                    protocol.handle_event(Event::TimerExpired { now: clock.now() })
+                }
+                Some(Action::Deliver(output)) => return Ok(output),
+                // A previous error interrupted a receive, treat it as having timed out
+                None => protocol.handle_event(Event::TimerExpired { now: clock.now() }),
+            }
+        }
+    }
+
+    /// Wait for the falling edge of the WTR signal on the GPIO pin, which indicates that a
+    /// receive or transmit completed
+    ///
+    /// If it does not complete within `timeout_us` microseconds the radio is returned to standby.
+    async fn wait_wtr<D>(
+        &mut self,
+        delay: &mut D,
+        timeout_us: u32,
+    ) -> Result<(), Error<SPI::Error, P::Error>>
+    where
+        D: embedded_hal_async::delay::DelayUs,
+    {
+        match select(
+            self.gpio.wait_for_falling_edge(),
+            delay.delay_us(timeout_us),
+        )
+        .await
+        {
+            Either::First(res) => res.map_err(Error::Pin),
+            Either::Second(()) => {
+                self.radio
+                    .command(Command::Strobe(Strobe::Standby))
+                    .await
+                    .map_err(Error::Spi)?;
+                Err(Error::Timeout)
+            }
+        }
+    }
+
+    /// Read the status of a completed receive and the packet out of the FIFO
+    async fn read_frame(
+        &mut self,
+        timestamp: Instant,
+    ) -> Result<(RawFrame, RxStatus), Error<SPI::Error, P::Error>> {
+        let mode = self.radio.read_reg::<Mode>().await.map_err(Error::Spi)?;
+        let rssi = self
+            .radio
+            .read_reg::<registers::Rssi>()
+            .await
+            .map_err(Error::Spi)?;
+        let status = RxStatus {
+            crc_error: mode.crc_error,
+            fec_error: mode.fec_error,
+            data_ready: !mode.trx_enabled,
+            timestamp,
+            channel: self.channel,
+            rssi: Rssi::from_raw(rssi.value),
+        };
+
+        let mut frame = RawFrame::default();
+        self.radio
+            .command(Command::Strobe(Strobe::FifoReadPointerReset))
+            .await
+            .map_err(Error::Spi)?;
+        self.radio
+            .rx(frame.as_bytes_mut())
+            .await
+            .map_err(Error::Spi)?;
+
+        Ok((frame, status))
+    }
+}
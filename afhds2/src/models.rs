@@ -0,0 +1,232 @@
+//! Storage of every transmitter a receiver has been bound to
+//!
+//! Like the model memory on a transmitter, a [`ModelMemory`] lets a single receiver stay bound to
+//! a few transmitters at once. A [`Receiver`](crate::Receiver) created with
+//! [`from_models`](crate::Receiver::from_models) listens for all of them after power-up, and
+//! follows whichever it hears from first.
+
+use crate::{
+    bind::BindResult,
+    packet::{NUM_BIND_OPTIONS, NUM_CONTROL_CHANNELS, NUM_HOP_CHANNELS},
+    store::{Record, MAX_RECORD_SIZE},
+    ChannelCount, Failsafe,
+};
+
+/// The most transmitters a [`ModelMemory`] can hold
+pub const MAX_MODELS: usize = 4;
+
+/// The failsafe position stored for a channel without one, as in a
+/// [`FailsafePacket`](crate::packet::FailsafePacket)
+const NO_FAILSAFE: u16 = 0xFFFF;
+
+/// A present flag, the transmitter ID, hop table, options, failsafe positions and channel count
+const ENCODED_MODEL_SIZE: usize =
+    1 + 4 + NUM_HOP_CHANNELS + NUM_BIND_OPTIONS + NUM_CONTROL_CHANNELS * 2 + 1;
+
+/// A present flag and ID for the active transmitter, followed by every model
+const ENCODED_SIZE: usize = 1 + 4 + MAX_MODELS * ENCODED_MODEL_SIZE;
+
+/// Everything needed to receive from a single bound transmitter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Model {
+    /// The result of binding with the transmitter
+    pub bind: BindResult,
+    /// The failsafe positions last received from the transmitter
+    pub failsafe: Failsafe,
+    /// How many channels the transmitter sends
+    pub channel_count: ChannelCount,
+}
+
+impl Model {
+    /// Create a new [`Model`] for a freshly bound transmitter, without any failsafe positions and
+    /// expecting 14 channels
+    pub fn new(bind: BindResult) -> Self {
+        Self {
+            bind,
+            failsafe: Failsafe::default(),
+            channel_count: ChannelCount::default(),
+        }
+    }
+
+    /// The ID of the transmitter
+    pub const fn transmitter_id(&self) -> u32 {
+        self.bind.transmitter_id
+    }
+}
+
+/// A small table of bound transmitters, one of which can be selected as the active one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ModelMemory {
+    models: [Option<Model>; MAX_MODELS],
+    active: Option<u32>,
+}
+
+impl ModelMemory {
+    /// Create a new, empty, [`ModelMemory`]
+    pub const fn new() -> Self {
+        Self {
+            models: [None; MAX_MODELS],
+            active: None,
+        }
+    }
+
+    /// Store a newly bound transmitter and select it, returning it back if there is no room left
+    ///
+    /// Binding with a transmitter that is already stored replaces it.
+    pub fn add(&mut self, bind: BindResult) -> Result<(), BindResult> {
+        let slot = match self.position(bind.transmitter_id) {
+            Some(slot) => slot,
+            None => self.models.iter().position(Option::is_none).ok_or(bind)?,
+        };
+        self.models[slot] = Some(Model::new(bind));
+        self.active = Some(bind.transmitter_id);
+        Ok(())
+    }
+
+    /// Remove the transmitter with the provided ID, returning it if it was stored
+    pub fn forget(&mut self, transmitter_id: u32) -> Option<Model> {
+        if self.active == Some(transmitter_id) {
+            self.active = None;
+        }
+        self.models[self.position(transmitter_id)?].take()
+    }
+
+    /// Select the transmitter with the provided ID as the active one, returning false if it is not
+    /// stored
+    pub fn select(&mut self, transmitter_id: u32) -> bool {
+        let stored = self.position(transmitter_id).is_some();
+        if stored {
+            self.active = Some(transmitter_id);
+        }
+        stored
+    }
+
+    /// The active transmitter, if one has been selected
+    pub fn active(&self) -> Option<&Model> {
+        self.get(self.active?)
+    }
+
+    /// The transmitter with the provided ID, if it is stored
+    pub fn get(&self, transmitter_id: u32) -> Option<&Model> {
+        self.models[self.position(transmitter_id)?].as_ref()
+    }
+
+    /// The transmitter with the provided ID, if it is stored
+    ///
+    /// This can be used to save the failsafe positions a transmitter sends once bound.
+    pub fn get_mut(&mut self, transmitter_id: u32) -> Option<&mut Model> {
+        self.models[self.position(transmitter_id)?].as_mut()
+    }
+
+    /// Every stored transmitter
+    pub fn iter(&self) -> impl Iterator<Item = &Model> {
+        self.models.iter().flatten()
+    }
+
+    /// The number of stored transmitters
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Returns true if no transmitters are stored
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if there is no room for another transmitter
+    pub fn is_full(&self) -> bool {
+        self.len() == MAX_MODELS
+    }
+
+    fn position(&self, transmitter_id: u32) -> Option<usize> {
+        self.models
+            .iter()
+            .position(|model| model.is_some_and(|model| model.transmitter_id() == transmitter_id))
+    }
+}
+
+impl Model {
+    fn encode(&self, bytes: &mut [u8]) {
+        let (id, rest) = bytes.split_at_mut(4);
+        id.copy_from_slice(&self.transmitter_id().to_le_bytes());
+        let (hop_table, rest) = rest.split_at_mut(NUM_HOP_CHANNELS);
+        hop_table.copy_from_slice(&self.bind.hop_table);
+        let (options, rest) = rest.split_at_mut(NUM_BIND_OPTIONS);
+        options.copy_from_slice(&self.bind.options);
+        let (failsafe, rest) = rest.split_at_mut(NUM_CONTROL_CHANNELS * 2);
+        for (bytes, position) in failsafe.chunks_exact_mut(2).zip(self.failsafe.positions()) {
+            bytes.copy_from_slice(&position.unwrap_or(NO_FAILSAFE).to_le_bytes());
+        }
+        rest[0] = self.channel_count.num_channels() as u8;
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let (id, rest) = bytes.split_at(4);
+        let (hop_table, rest) = rest.split_at(NUM_HOP_CHANNELS);
+        let (options, rest) = rest.split_at(NUM_BIND_OPTIONS);
+        let (failsafe, rest) = rest.split_at(NUM_CONTROL_CHANNELS * 2);
+
+        let mut positions = [None; NUM_CONTROL_CHANNELS];
+        for (position, bytes) in positions.iter_mut().zip(failsafe.chunks_exact(2)) {
+            let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+            *position = (value != NO_FAILSAFE).then_some(value);
+        }
+        Some(Self {
+            bind: BindResult {
+                transmitter_id: u32::from_le_bytes(id.try_into().ok()?),
+                hop_table: hop_table.try_into().ok()?,
+                options: options.try_into().ok()?,
+            },
+            failsafe: Failsafe::new(positions),
+            channel_count: ChannelCount::from_len(usize::from(*rest.first()?))?,
+        })
+    }
+}
+
+impl Record for ModelMemory {
+    const KEY: u8 = 0x01;
+    const VERSION: u8 = 1;
+
+    fn encode(&self, bytes: &mut [u8; MAX_RECORD_SIZE]) -> usize {
+        let (active, models) = bytes[..ENCODED_SIZE].split_at_mut(5);
+        active[0] = self.active.is_some() as u8;
+        active[1..].copy_from_slice(&self.active.unwrap_or_default().to_le_bytes());
+        for (bytes, model) in models
+            .chunks_exact_mut(ENCODED_MODEL_SIZE)
+            .zip(&self.models)
+        {
+            bytes.fill(0);
+            if let Some(model) = model {
+                bytes[0] = 1;
+                model.encode(&mut bytes[1..]);
+            }
+        }
+        ENCODED_SIZE
+    }
+
+    fn decode(_version: u8, bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != ENCODED_SIZE {
+            return None;
+        }
+        let (active, models) = bytes.split_at(5);
+
+        let mut memory = Self::new();
+        for (model, bytes) in memory
+            .models
+            .iter_mut()
+            .zip(models.chunks_exact(ENCODED_MODEL_SIZE))
+        {
+            if bytes[0] != 0 {
+                *model = Some(Model::decode(&bytes[1..])?);
+            }
+        }
+        if active[0] != 0 {
+            memory.select(u32::from_le_bytes(active[1..].try_into().ok()?));
+        }
+        Some(memory)
+    }
+}
@@ -0,0 +1,661 @@
+//! Parsing and serialization of the packets sent by an AFHDS2A transmitter, and the telemetry
+//! sent back by the receiver
+
+use crate::{
+    telemetry::{Sensor, SensorType, Sensors, MAX_SENSORS},
+    ChannelCount, Channels, RawFrame, PACKET_SIZE,
+};
+
+/// The number of channel words carried by a sticks packet
+pub const NUM_CONTROL_CHANNELS: usize = 14;
+
+pub(crate) const PACKET_ID_BIND1: u8 = 0xBB;
+pub(crate) const PACKET_ID_BIND2: u8 = 0xBC;
+pub(crate) const PACKET_ID_STICKS: u8 = 0x58;
+pub(crate) const PACKET_ID_FAILSAFE: u8 = 0x56;
+pub(crate) const PACKET_ID_SETTINGS: u8 = 0xAA;
+/// Telemetry sent back by the receiver shares its packet type with settings packets
+const PACKET_ID_TELEMETRY: u8 = 0xAA;
+
+/// The value of byte 9 that marks a [`PACKET_ID_SETTINGS`] packet as carrying receiver settings
+const SETTINGS_MARKER: u8 = 0xFD;
+
+/// The offset of the first sensor entry in a telemetry packet, each entry taking 4 bytes
+const TELEMETRY_SENSORS_OFFSET: usize = 9;
+
+/// The sensor type that marks the end of the sensor entries in a telemetry packet
+const TELEMETRY_END: u8 = 0xFF;
+
+/// Output flag bits in a settings packet
+const SETTINGS_PPM: u8 = 0x01;
+const SETTINGS_SBUS: u8 = 0x02;
+
+/// The value sent for a channel that does not have a failsafe position
+const FAILSAFE_DISABLED: u16 = 0xFFFF;
+
+/// The reasons a packet can fail to parse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PacketError {
+    /// The packet was shorter than its type requires
+    TooShort,
+    /// The packet type is not one that is understood
+    UnknownType(u8),
+}
+
+/// A packet sent by an AFHDS2A transmitter
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TransmitterPacket {
+    Sticks(SticksPacket),
+    Bind(BindPacket),
+    Failsafe(FailsafePacket),
+    Settings(SettingsPacket),
+}
+
+impl TransmitterPacket {
+    /// Parse a packet from the provided bytes, based on the packet type in the first byte
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PacketError> {
+        match bytes.first() {
+            Some(&PACKET_ID_STICKS) => SticksPacket::from_bytes(bytes).map(Self::Sticks),
+            Some(&PACKET_ID_BIND1 | &PACKET_ID_BIND2) => {
+                BindPacket::from_bytes(bytes).map(Self::Bind)
+            }
+            Some(&PACKET_ID_FAILSAFE) => FailsafePacket::from_bytes(bytes).map(Self::Failsafe),
+            Some(&PACKET_ID_SETTINGS) => SettingsPacket::from_bytes(bytes).map(Self::Settings),
+            Some(&packet_type) => Err(PacketError::UnknownType(packet_type)),
+            None => Err(PacketError::TooShort),
+        }
+    }
+
+    /// Parse a packet received by the radio
+    pub fn from_frame(frame: &RawFrame) -> Result<Self, PacketError> {
+        Self::from_bytes(frame.as_bytes())
+    }
+
+    /// Write the packet into the provided buffer
+    pub fn to_bytes(&self, bytes: &mut [u8; PACKET_SIZE]) {
+        match self {
+            Self::Sticks(packet) => packet.to_bytes(bytes),
+            Self::Bind(packet) => packet.to_bytes(bytes),
+            Self::Failsafe(packet) => packet.to_bytes(bytes),
+            Self::Settings(packet) => packet.to_bytes(bytes),
+        }
+    }
+
+    /// Build a frame holding the packet, ready to be transmitted by the radio
+    pub fn to_frame(&self) -> RawFrame {
+        let mut frame = RawFrame::default();
+        self.to_bytes(frame.as_bytes_mut());
+        frame
+    }
+
+    /// The ID of the transmitter that sent the packet
+    pub const fn transmitter_id(&self) -> u32 {
+        match self {
+            Self::Sticks(packet) => packet.transmitter_id,
+            Self::Bind(packet) => packet.transmitter_id,
+            Self::Failsafe(packet) => packet.transmitter_id,
+            Self::Settings(packet) => packet.transmitter_id,
+        }
+    }
+
+    /// The ID of the receiver the packet is addressed to
+    pub const fn receiver_id(&self) -> u32 {
+        match self {
+            Self::Sticks(packet) => packet.receiver_id,
+            Self::Bind(packet) => packet.receiver_id,
+            Self::Failsafe(packet) => packet.receiver_id,
+            Self::Settings(packet) => packet.receiver_id,
+        }
+    }
+}
+
+/// A packet carrying the current value of every control channel
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SticksPacket {
+    transmitter_id: u32,
+    receiver_id: u32,
+    sticks: [u16; NUM_CONTROL_CHANNELS],
+}
+
+impl SticksPacket {
+    /// Create a new [`SticksPacket`]
+    pub const fn new(
+        transmitter_id: u32,
+        receiver_id: u32,
+        sticks: [u16; NUM_CONTROL_CHANNELS],
+    ) -> Self {
+        Self {
+            transmitter_id,
+            receiver_id,
+            sticks,
+        }
+    }
+
+    /// Create a new [`SticksPacket`] carrying the provided channels
+    pub fn from_channels(transmitter_id: u32, receiver_id: u32, channels: Channels) -> Self {
+        Self::new(transmitter_id, receiver_id, channels.to_words())
+    }
+
+    /// Parse a sticks packet from the provided bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PacketError> {
+        expect_type(bytes, &[PACKET_ID_STICKS])?;
+        let transmitter_id = read_u32(bytes, 1)?;
+        let receiver_id = read_u32(bytes, 5)?;
+
+        let mut sticks = [0u16; NUM_CONTROL_CHANNELS];
+        for (i, stick) in sticks.iter_mut().enumerate() {
+            *stick = read_u16(bytes, 9 + i * 2)?;
+        }
+
+        Ok(Self {
+            transmitter_id,
+            receiver_id,
+            sticks,
+        })
+    }
+
+    /// Write the packet into the provided buffer
+    pub fn to_bytes(&self, bytes: &mut [u8; PACKET_SIZE]) {
+        write_header(
+            bytes,
+            PACKET_ID_STICKS,
+            self.transmitter_id,
+            self.receiver_id,
+        );
+        for (i, stick) in self.sticks.iter().enumerate() {
+            write_u16(bytes, 9 + i * 2, *stick);
+        }
+    }
+
+    /// The ID of the transmitter that sent the packet
+    pub const fn transmitter_id(&self) -> u32 {
+        self.transmitter_id
+    }
+
+    /// The ID of the receiver the packet is addressed to
+    pub const fn receiver_id(&self) -> u32 {
+        self.receiver_id
+    }
+
+    /// The raw value of every channel word
+    ///
+    /// These are the first 14 channels as-is, unless the transmitter sends more channels than
+    /// that, in which case [`channels`](Self::channels) should be used to unpack them.
+    pub const fn sticks(&self) -> &[u16; NUM_CONTROL_CHANNELS] {
+        &self.sticks
+    }
+
+    /// The raw value of the channel word at the provided index, if it exists
+    pub fn channel(&self, index: usize) -> Option<u16> {
+        self.sticks.get(index).copied()
+    }
+
+    /// Unpack the value of every channel, for a transmitter sending `count` channels
+    pub fn channels(&self, count: ChannelCount) -> Channels {
+        Channels::from_words(&self.sticks, count)
+    }
+}
+
+/// The number of channels in the AFHDS2A hopping sequence
+pub const NUM_HOP_CHANNELS: usize = 16;
+
+/// The number of receiver option bytes carried by a bind packet
+pub const NUM_BIND_OPTIONS: usize = 10;
+
+/// The receiver ID used in bind packets before the transmitter knows the receiver
+pub const UNKNOWN_RECEIVER_ID: u32 = 0xFFFF_FFFF;
+
+/// The two kinds of packet exchanged while binding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BindPacketType {
+    /// Sent by the transmitter to announce that it is binding (0xBB)
+    Bind1,
+    /// Exchanged by both sides for the remainder of the bind (0xBC)
+    Bind2,
+}
+
+impl BindPacketType {
+    const fn id(&self) -> u8 {
+        match self {
+            Self::Bind1 => PACKET_ID_BIND1,
+            Self::Bind2 => PACKET_ID_BIND2,
+        }
+    }
+}
+
+/// A packet exchanged while binding a receiver to a transmitter
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BindPacket {
+    packet_type: BindPacketType,
+    transmitter_id: u32,
+    receiver_id: u32,
+    stage: u8,
+    hop_table: [u8; NUM_HOP_CHANNELS],
+    options: [u8; NUM_BIND_OPTIONS],
+}
+
+impl BindPacket {
+    /// Create a new [`BindPacket`]
+    pub const fn new(
+        packet_type: BindPacketType,
+        transmitter_id: u32,
+        receiver_id: u32,
+        stage: u8,
+        hop_table: [u8; NUM_HOP_CHANNELS],
+        options: [u8; NUM_BIND_OPTIONS],
+    ) -> Self {
+        Self {
+            packet_type,
+            transmitter_id,
+            receiver_id,
+            stage,
+            hop_table,
+            options,
+        }
+    }
+
+    /// Parse a bind packet from the provided bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PacketError> {
+        expect_type(bytes, &[PACKET_ID_BIND1, PACKET_ID_BIND2])?;
+        let packet_type = if bytes[0] == PACKET_ID_BIND1 {
+            BindPacketType::Bind1
+        } else {
+            BindPacketType::Bind2
+        };
+        let transmitter_id = read_u32(bytes, 1)?;
+        let receiver_id = read_u32(bytes, 5)?;
+        let stage = read_u8(bytes, 9)?;
+
+        let mut hop_table = [0u8; NUM_HOP_CHANNELS];
+        for (i, channel) in hop_table.iter_mut().enumerate() {
+            *channel = read_u8(bytes, 11 + i)?;
+        }
+
+        let mut options = [0u8; NUM_BIND_OPTIONS];
+        for (i, option) in options.iter_mut().enumerate() {
+            *option = read_u8(bytes, 11 + NUM_HOP_CHANNELS + i)?;
+        }
+
+        Ok(Self {
+            packet_type,
+            transmitter_id,
+            receiver_id,
+            stage,
+            hop_table,
+            options,
+        })
+    }
+
+    /// Write the packet into the provided buffer
+    pub fn to_bytes(&self, bytes: &mut [u8; PACKET_SIZE]) {
+        write_header(
+            bytes,
+            self.packet_type.id(),
+            self.transmitter_id,
+            self.receiver_id,
+        );
+        bytes[9] = self.stage;
+        bytes[10] = 0x00;
+        bytes[11..11 + NUM_HOP_CHANNELS].copy_from_slice(&self.hop_table);
+        bytes[11 + NUM_HOP_CHANNELS..].copy_from_slice(&self.options);
+    }
+
+    /// Whether this is a [`Bind1`](BindPacketType::Bind1) or [`Bind2`](BindPacketType::Bind2)
+    /// packet
+    pub const fn packet_type(&self) -> BindPacketType {
+        self.packet_type
+    }
+
+    /// The ID of the transmitter that sent the packet
+    pub const fn transmitter_id(&self) -> u32 {
+        self.transmitter_id
+    }
+
+    /// The ID of the receiver the packet is addressed to, [`UNKNOWN_RECEIVER_ID`] until the
+    /// transmitter has heard from a receiver
+    pub const fn receiver_id(&self) -> u32 {
+        self.receiver_id
+    }
+
+    /// The stage of the bind exchange this packet belongs to
+    pub const fn stage(&self) -> u8 {
+        self.stage
+    }
+
+    /// The channels the transmitter hops between once bound
+    pub const fn hop_table(&self) -> &[u8; NUM_HOP_CHANNELS] {
+        &self.hop_table
+    }
+
+    /// The receiver option bytes
+    pub const fn options(&self) -> &[u8; NUM_BIND_OPTIONS] {
+        &self.options
+    }
+}
+
+/// A packet carrying the failsafe position configured on the transmitter for each channel
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FailsafePacket {
+    transmitter_id: u32,
+    receiver_id: u32,
+    failsafe: [Option<u16>; NUM_CONTROL_CHANNELS],
+}
+
+impl FailsafePacket {
+    /// Create a new [`FailsafePacket`], channels set to `None` have failsafe disabled
+    pub const fn new(
+        transmitter_id: u32,
+        receiver_id: u32,
+        failsafe: [Option<u16>; NUM_CONTROL_CHANNELS],
+    ) -> Self {
+        Self {
+            transmitter_id,
+            receiver_id,
+            failsafe,
+        }
+    }
+
+    /// Parse a failsafe packet from the provided bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PacketError> {
+        expect_type(bytes, &[PACKET_ID_FAILSAFE])?;
+        let transmitter_id = read_u32(bytes, 1)?;
+        let receiver_id = read_u32(bytes, 5)?;
+
+        let mut failsafe = [None; NUM_CONTROL_CHANNELS];
+        for (i, value) in failsafe.iter_mut().enumerate() {
+            *value = match read_u16(bytes, 9 + i * 2)? {
+                FAILSAFE_DISABLED => None,
+                position => Some(position),
+            };
+        }
+
+        Ok(Self {
+            transmitter_id,
+            receiver_id,
+            failsafe,
+        })
+    }
+
+    /// Write the packet into the provided buffer
+    ///
+    /// A failsafe position of `0xFFFF` can not be told apart from a disabled channel, so is read
+    /// back as `None`.
+    pub fn to_bytes(&self, bytes: &mut [u8; PACKET_SIZE]) {
+        write_header(
+            bytes,
+            PACKET_ID_FAILSAFE,
+            self.transmitter_id,
+            self.receiver_id,
+        );
+        for (i, position) in self.failsafe.iter().enumerate() {
+            write_u16(bytes, 9 + i * 2, position.unwrap_or(FAILSAFE_DISABLED));
+        }
+    }
+
+    /// The ID of the transmitter that sent the packet
+    pub const fn transmitter_id(&self) -> u32 {
+        self.transmitter_id
+    }
+
+    /// The ID of the receiver the packet is addressed to
+    pub const fn receiver_id(&self) -> u32 {
+        self.receiver_id
+    }
+
+    /// The failsafe position of every channel, `None` if failsafe is disabled for the channel
+    pub const fn failsafe(&self) -> &[Option<u16>; NUM_CONTROL_CHANNELS] {
+        &self.failsafe
+    }
+
+    /// The failsafe position of the channel at the provided index, if it is enabled
+    pub fn channel(&self, index: usize) -> Option<u16> {
+        self.failsafe.get(index).copied().flatten()
+    }
+}
+
+/// A packet carrying the settings the transmitter wants the receiver to use for its outputs
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SettingsPacket {
+    transmitter_id: u32,
+    receiver_id: u32,
+    servo_hz: u16,
+    ppm: bool,
+    sbus: bool,
+}
+
+impl SettingsPacket {
+    /// Create a new [`SettingsPacket`]
+    pub const fn new(
+        transmitter_id: u32,
+        receiver_id: u32,
+        servo_hz: u16,
+        ppm: bool,
+        sbus: bool,
+    ) -> Self {
+        Self {
+            transmitter_id,
+            receiver_id,
+            servo_hz,
+            ppm,
+            sbus,
+        }
+    }
+
+    /// Parse a settings packet from the provided bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PacketError> {
+        expect_type(bytes, &[PACKET_ID_SETTINGS])?;
+        if read_u8(bytes, 9)? != SETTINGS_MARKER {
+            return Err(PacketError::UnknownType(PACKET_ID_SETTINGS));
+        }
+        let transmitter_id = read_u32(bytes, 1)?;
+        let receiver_id = read_u32(bytes, 5)?;
+        let servo_hz = read_u16(bytes, 11)?;
+        let outputs = read_u8(bytes, 13)?;
+
+        Ok(Self {
+            transmitter_id,
+            receiver_id,
+            servo_hz,
+            ppm: outputs & SETTINGS_PPM != 0,
+            sbus: outputs & SETTINGS_SBUS != 0,
+        })
+    }
+
+    /// Write the packet into the provided buffer
+    pub fn to_bytes(&self, bytes: &mut [u8; PACKET_SIZE]) {
+        write_header(
+            bytes,
+            PACKET_ID_SETTINGS,
+            self.transmitter_id,
+            self.receiver_id,
+        );
+        bytes[9] = SETTINGS_MARKER;
+        bytes[10] = 0xFF;
+        write_u16(bytes, 11, self.servo_hz);
+        bytes[13] = (self.ppm as u8 * SETTINGS_PPM) | (self.sbus as u8 * SETTINGS_SBUS);
+        bytes[14] = 0x00;
+        bytes[15..].fill(0xFF);
+    }
+
+    /// The ID of the transmitter that sent the packet
+    pub const fn transmitter_id(&self) -> u32 {
+        self.transmitter_id
+    }
+
+    /// The ID of the receiver the packet is addressed to
+    pub const fn receiver_id(&self) -> u32 {
+        self.receiver_id
+    }
+
+    /// The frequency the receiver should update its servo outputs at
+    pub const fn servo_hz(&self) -> u16 {
+        self.servo_hz
+    }
+
+    /// Whether the receiver should output PPM rather than PWM
+    pub const fn ppm(&self) -> bool {
+        self.ppm
+    }
+
+    /// Whether the receiver should output SBUS rather than IBUS
+    pub const fn sbus(&self) -> bool {
+        self.sbus
+    }
+}
+
+/// A packet sent back to the transmitter by the receiver, carrying sensor readings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TelemetryPacket {
+    transmitter_id: u32,
+    receiver_id: u32,
+    sensors: Sensors,
+}
+
+impl TelemetryPacket {
+    /// Create a new [`TelemetryPacket`]
+    pub const fn new(transmitter_id: u32, receiver_id: u32, sensors: Sensors) -> Self {
+        Self {
+            transmitter_id,
+            receiver_id,
+            sensors,
+        }
+    }
+
+    /// Parse a telemetry packet from the provided bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PacketError> {
+        expect_type(bytes, &[PACKET_ID_TELEMETRY])?;
+        // Settings packets share the same packet type, but never carry a sensor of this type
+        if read_u8(bytes, TELEMETRY_SENSORS_OFFSET)? == SETTINGS_MARKER {
+            return Err(PacketError::UnknownType(PACKET_ID_TELEMETRY));
+        }
+        let transmitter_id = read_u32(bytes, 1)?;
+        let receiver_id = read_u32(bytes, 5)?;
+
+        let mut sensors = Sensors::new();
+        for i in 0..MAX_SENSORS {
+            let offset = TELEMETRY_SENSORS_OFFSET + i * 4;
+            let sensor_type = read_u8(bytes, offset)?;
+            if sensor_type == TELEMETRY_END {
+                break;
+            }
+            // There is always room, as there are no more entries than fit in a packet
+            let _ = sensors.push(Sensor::new(
+                SensorType::from_raw(sensor_type),
+                read_u8(bytes, offset + 1)?,
+                read_u16(bytes, offset + 2)?,
+            ));
+        }
+
+        Ok(Self {
+            transmitter_id,
+            receiver_id,
+            sensors,
+        })
+    }
+
+    /// Parse a telemetry packet received by the radio
+    pub fn from_frame(frame: &RawFrame) -> Result<Self, PacketError> {
+        Self::from_bytes(frame.as_bytes())
+    }
+
+    /// Write the packet into the provided buffer
+    pub fn to_bytes(&self, bytes: &mut [u8; PACKET_SIZE]) {
+        write_header(
+            bytes,
+            PACKET_ID_TELEMETRY,
+            self.transmitter_id,
+            self.receiver_id,
+        );
+        bytes[TELEMETRY_SENSORS_OFFSET..].fill(TELEMETRY_END);
+        for (i, sensor) in self.sensors.as_slice().iter().enumerate() {
+            let offset = TELEMETRY_SENSORS_OFFSET + i * 4;
+            bytes[offset] = sensor.sensor_type.raw();
+            bytes[offset + 1] = sensor.instance;
+            write_u16(bytes, offset + 2, sensor.value);
+        }
+    }
+
+    /// Build a frame holding the packet, ready to be transmitted by the radio
+    pub fn to_frame(&self) -> RawFrame {
+        let mut frame = RawFrame::default();
+        self.to_bytes(frame.as_bytes_mut());
+        frame
+    }
+
+    /// The ID of the transmitter the packet is addressed to
+    pub const fn transmitter_id(&self) -> u32 {
+        self.transmitter_id
+    }
+
+    /// The ID of the receiver that sent the packet
+    pub const fn receiver_id(&self) -> u32 {
+        self.receiver_id
+    }
+
+    /// The sensor readings carried by the packet
+    pub const fn sensors(&self) -> &Sensors {
+        &self.sensors
+    }
+}
+
+fn expect_type(bytes: &[u8], packet_types: &[u8]) -> Result<(), PacketError> {
+    let packet_type = read_u8(bytes, 0)?;
+    if packet_types.contains(&packet_type) {
+        Ok(())
+    } else {
+        Err(PacketError::UnknownType(packet_type))
+    }
+}
+
+fn read_u8(bytes: &[u8], offset: usize) -> Result<u8, PacketError> {
+    bytes.get(offset).copied().ok_or(PacketError::TooShort)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, PacketError> {
+    match bytes.get(offset..offset + 2) {
+        Some(&[b0, b1]) => Ok(u16::from_le_bytes([b0, b1])),
+        _ => Err(PacketError::TooShort),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, PacketError> {
+    match bytes.get(offset..offset + 4) {
+        Some(&[b0, b1, b2, b3]) => Ok(u32::from_le_bytes([b0, b1, b2, b3])),
+        _ => Err(PacketError::TooShort),
+    }
+}
+
+fn write_header(
+    bytes: &mut [u8; PACKET_SIZE],
+    packet_type: u8,
+    transmitter_id: u32,
+    receiver_id: u32,
+) {
+    bytes[0] = packet_type;
+    write_u32(bytes, 1, transmitter_id);
+    write_u32(bytes, 5, receiver_id);
+}
+
+fn write_u16(bytes: &mut [u8; PACKET_SIZE], offset: usize, value: u16) {
+    bytes[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_u32(bytes: &mut [u8; PACKET_SIZE], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
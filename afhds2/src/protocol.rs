@@ -0,0 +1,84 @@
+//! The events and actions shared by the sans-IO protocol state machines
+//!
+//! Both the [`Receiver`](crate::Receiver) and [`Transmitter`](crate::Transmitter) are fed
+//! [`Event`]s describing what the radio did, and queue up [`Action`]s for the radio to perform
+//! next. The [`Afhds2`](crate::Afhds2) driver performs these actions on real hardware.
+
+use crate::{time::Instant, RawFrame, RxStatus};
+
+/// The most actions that can be queued in response to a single event
+const MAX_ACTIONS: usize = 6;
+
+/// Something that happened on the radio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// A packet was received, along with the state of the radio when it was received
+    FrameReceived {
+        frame: &'a RawFrame,
+        status: RxStatus,
+    },
+    /// The deadline of the last [`Action::Receive`] or [`Action::Wait`] passed without a packet
+    /// being received
+    TimerExpired { now: Instant },
+}
+
+/// Something the radio should do, or an output `O` for the application
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Action<O> {
+    /// Tune the PLL to the provided channel
+    Tune(u8),
+    /// Transmit the provided packet on the currently tuned channel
+    Transmit(RawFrame),
+    /// Strobe RX and wait for a packet until `deadline`, reporting the result as an [`Event`]
+    Receive { deadline: Instant },
+    /// Do nothing until `until`, then report [`Event::TimerExpired`]
+    Wait { until: Instant },
+    /// Hand the provided output to the application
+    Deliver(O),
+}
+
+/// A protocol state machine that can be run by the [`Afhds2`](crate::Afhds2) driver
+pub(crate) trait Protocol {
+    type Output: Copy;
+
+    /// The next action to perform, if any
+    fn poll_action(&mut self) -> Option<Action<Self::Output>>;
+
+    /// Handle something that happened on the radio
+    fn handle_event(&mut self, event: Event<'_>);
+}
+
+/// A fixed capacity queue of the actions waiting to be performed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Actions<O> {
+    actions: [Option<Action<O>>; MAX_ACTIONS],
+    head: usize,
+    len: usize,
+}
+
+impl<O: Copy> Actions<O> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            actions: [None; MAX_ACTIONS],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, action: Action<O>) {
+        assert!(self.len < MAX_ACTIONS, "too many actions queued");
+        self.actions[(self.head + self.len) % MAX_ACTIONS] = Some(action);
+        self.len += 1;
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Action<O>> {
+        if self.len == 0 {
+            return None;
+        }
+        let action = self.actions[self.head].take();
+        self.head = (self.head + 1) % MAX_ACTIONS;
+        self.len -= 1;
+        action
+    }
+}
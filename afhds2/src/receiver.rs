@@ -0,0 +1,501 @@
+//! The AFHDS2A receive protocol, independent of any radio hardware
+//!
+//! The [`Receiver`] is a pure state machine covering binding, following the hop sequence and
+//! failsafe. It is fed [`Event`]s describing what the radio did and queues up [`Action`]s for
+//! the radio to perform next, which makes it possible to exercise the protocol without an A7105.
+//! It never asks the radio to [`Wait`](Action::Wait).
+//!
+//! A receiver created from a [`ModelMemory`] searches every stored transmitter in turn, dwelling
+//! on one channel of each hop table, and follows whichever stored transmitter it hears from first.
+//!
+//! Once bound, only packets carrying both the bound transmitter's ID and this receiver's ID are
+//! acted on. Anything else is counted in [`LinkStats`] and otherwise ignored.
+//!
+//! Once [`Receiver::update_telemetry`] has been called, every sticks packet is answered with a
+//! telemetry packet carrying the latest sensor readings.
+//! [`Afhds2::poll_receiver`](crate::Afhds2::poll_receiver) executes the actions on real hardware.
+
+use crate::{
+    bind::{BindAction, BindResult, Binder, BIND_LISTEN_US},
+    hopping::HopTracker,
+    packet::{BindPacket, TelemetryPacket, TransmitterPacket},
+    protocol::{Action, Actions, Event, Protocol},
+    rx_pll_channel,
+    telemetry::{Sensor, Sensors, TelemetrySource},
+    time::Instant,
+    ChannelCount, Channels, Failsafe, LinkStats, Model, ModelMemory, RawFrame, RxStatus,
+};
+
+/// How long without a sticks packet before the failsafe positions are applied
+pub const FAILSAFE_TIMEOUT_US: u32 = 1_000_000;
+
+/// Something the application needs to know about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Output {
+    /// The bind completed, the result should be stored to receive from the transmitter again
+    Bound(BindResult),
+    /// The transmitter with the provided ID was heard while searching the [`ModelMemory`], and
+    /// is now being followed
+    Selected(u32),
+    /// New values were received for every control channel
+    Channels(Channels),
+    /// The link to the transmitter was lost and the failsafe positions have been applied to the
+    /// last received channel values
+    Failsafe(Channels),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Binding(Binder),
+    /// Listening for any of the transmitters in the model memory
+    Searching {
+        /// The position in the model memory of the transmitter being listened for
+        candidate: usize,
+        /// How many times every transmitter has been listened for, so that each search moves on
+        /// to a different channel of the hop tables
+        sweep: usize,
+        tracker: HopTracker,
+    },
+    Bound {
+        bind: BindResult,
+        tracker: HopTracker,
+    },
+}
+
+/// The receive side of the AFHDS2A protocol
+///
+/// Once created, the receiver always has at least one [`Action`] queued. Every action returned by
+/// [`poll_action`](Self::poll_action) should be performed in order, and the outcome of each
+/// [`Action::Receive`] reported with [`handle_event`](Self::handle_event).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Receiver {
+    receiver_id: u32,
+    mode: Mode,
+    models: ModelMemory,
+    deadline: Instant,
+    failsafe: Failsafe,
+    channel_count: ChannelCount,
+    sticks: Channels,
+    last_sticks: Option<Instant>,
+    failsafe_active: bool,
+    telemetry: Option<Sensors>,
+    stats: LinkStats,
+    actions: Actions<Output>,
+}
+
+impl Receiver {
+    /// Create a new [`Receiver`] that will bind to the first transmitter heard in bind mode
+    ///
+    /// The `receiver_id` should be unique to this receiver, see [`ReceiverId`](crate::ReceiverId).
+    pub fn bind(receiver_id: u32, now: Instant) -> Self {
+        let mut receiver = Self::new(receiver_id, Mode::Binding(Binder::new(receiver_id)), now);
+        receiver.listen_bind(now);
+        receiver
+    }
+
+    /// Create a new [`Receiver`] that will receive from a previously bound transmitter
+    pub fn bound(receiver_id: u32, result: &BindResult, now: Instant) -> Self {
+        let mut receiver = Self::new(receiver_id, Self::bound_mode(result, now), now);
+        receiver.listen_hop();
+        receiver
+    }
+
+    /// Create a new [`Receiver`] that will receive from whichever transmitter in `models` it hears
+    /// from first, starting with the active one
+    ///
+    /// With no transmitters stored, this falls back to binding like [`bind`](Self::bind).
+    pub fn from_models(receiver_id: u32, models: &ModelMemory, now: Instant) -> Self {
+        let Some(first) = models.active().or_else(|| models.iter().next()) else {
+            return Self::bind(receiver_id, now);
+        };
+
+        let candidate = models
+            .iter()
+            .position(|model| model.transmitter_id() == first.transmitter_id())
+            .unwrap_or_default();
+        let mode = Mode::Searching {
+            candidate,
+            sweep: 0,
+            tracker: HopTracker::new(first.bind.hop_table, now),
+        };
+        let mut receiver = Self::new(receiver_id, mode, now);
+        receiver.models = *models;
+        receiver.listen_hop();
+        receiver
+    }
+
+    fn new(receiver_id: u32, mode: Mode, now: Instant) -> Self {
+        Self {
+            receiver_id,
+            mode,
+            models: ModelMemory::new(),
+            deadline: now,
+            failsafe: Failsafe::default(),
+            channel_count: ChannelCount::default(),
+            sticks: Channels::default(),
+            last_sticks: None,
+            failsafe_active: false,
+            telemetry: None,
+            stats: LinkStats::new(now),
+            actions: Actions::new(),
+        }
+    }
+
+    const fn bound_mode(result: &BindResult, now: Instant) -> Mode {
+        Mode::Bound {
+            bind: *result,
+            tracker: HopTracker::new(result.hop_table, now),
+        }
+    }
+
+    /// The ID of this receiver
+    pub const fn receiver_id(&self) -> u32 {
+        self.receiver_id
+    }
+
+    /// Returns true once bound to a transmitter, including while searching for one of several
+    pub const fn is_bound(&self) -> bool {
+        !matches!(self.mode, Mode::Binding(_))
+    }
+
+    /// The hop tracker following the transmitter, once bound
+    pub const fn tracker(&self) -> Option<&HopTracker> {
+        match &self.mode {
+            Mode::Bound { tracker, .. } | Mode::Searching { tracker, .. } => Some(tracker),
+            Mode::Binding(_) => None,
+        }
+    }
+
+    /// The transmitter being followed, along with the failsafe positions and channel count in
+    /// use, which can be saved to a [`ModelMemory`]
+    pub fn model(&self) -> Option<Model> {
+        let Mode::Bound { bind, .. } = self.mode else {
+            return None;
+        };
+        Some(Model {
+            bind,
+            failsafe: self.failsafe,
+            channel_count: self.channel_count,
+        })
+    }
+
+    /// How many channels the transmitter is expected to send
+    pub const fn channel_count(&self) -> ChannelCount {
+        self.channel_count
+    }
+
+    /// Set how many channels the transmitter is expected to send, 14 by default
+    ///
+    /// This has to match the transmitter, as there is nothing in the packets to tell them apart.
+    pub fn set_channel_count(&mut self, count: ChannelCount) {
+        self.channel_count = count;
+        self.sticks = Channels::new(count);
+    }
+
+    /// The failsafe positions most recently received from the transmitter
+    pub const fn failsafe(&self) -> &Failsafe {
+        &self.failsafe
+    }
+
+    /// Returns true while the failsafe positions are applied
+    pub const fn is_failsafe_active(&self) -> bool {
+        self.failsafe_active
+    }
+
+    /// Statistics on the link to the transmitter, updated once bound
+    pub const fn link_stats(&self) -> &LinkStats {
+        &self.stats
+    }
+
+    /// Replace the sensor readings sent back to the transmitter with the latest from `source`
+    ///
+    /// Telemetry is only sent once this has been called. The signal strength of each sticks
+    /// packet and the packet error rate from the [`LinkStats`] are added to the readings when
+    /// there is room for them.
+    pub fn update_telemetry<S: TelemetrySource + ?Sized>(&mut self, source: &mut S) {
+        let mut sensors = Sensors::new();
+        source.read_sensors(&mut sensors);
+        self.telemetry = Some(sensors);
+    }
+
+    /// The next action to perform, if any
+    pub fn poll_action(&mut self) -> Option<Action<Output>> {
+        self.actions.pop()
+    }
+
+    /// Handle something that happened on the radio, queueing up the actions to perform next
+    pub fn handle_event(&mut self, event: Event<'_>) {
+        match (event, self.mode) {
+            (Event::FrameReceived { frame, status }, Mode::Binding(_)) => {
+                self.handle_bind_frame(frame, &status)
+            }
+            (Event::FrameReceived { frame, status }, Mode::Bound { .. }) => {
+                self.handle_hop_frame(frame, &status)
+            }
+            (Event::TimerExpired { now }, Mode::Binding(mut binder)) => {
+                binder.handle_timeout();
+                self.mode = Mode::Binding(binder);
+                self.listen_bind(now);
+            }
+            (Event::FrameReceived { frame, status }, Mode::Searching { .. }) => {
+                self.handle_search_frame(frame, &status)
+            }
+            (Event::TimerExpired { now }, Mode::Bound { .. }) => self.handle_hop_timeout(now),
+            (Event::TimerExpired { now }, Mode::Searching { .. }) => {
+                self.handle_search_timeout(now)
+            }
+        }
+    }
+
+    fn handle_bind_frame(&mut self, frame: &RawFrame, status: &RxStatus) {
+        let Mode::Binding(mut binder) = self.mode else {
+            return;
+        };
+
+        let packet = match BindPacket::from_bytes(frame.as_bytes()) {
+            Ok(packet) if status.is_valid() => packet,
+            _ => {
+                // Keep listening out the rest of the current dwell
+                self.actions.push(Action::Receive {
+                    deadline: self.deadline,
+                });
+                return;
+            }
+        };
+
+        match binder.handle_packet(&packet) {
+            BindAction::None => {
+                self.mode = Mode::Binding(binder);
+                self.actions.push(Action::Receive {
+                    deadline: self.deadline,
+                });
+            }
+            BindAction::Reply(reply) => {
+                self.mode = Mode::Binding(binder);
+                let mut frame = RawFrame::default();
+                reply.to_bytes(frame.as_bytes_mut());
+                self.actions.push(Action::Tune(binder.channel()));
+                self.actions.push(Action::Transmit(frame));
+                self.listen_bind(status.timestamp);
+            }
+            BindAction::Complete(result) => {
+                self.mode = Self::bound_mode(&result, status.timestamp);
+                self.actions.push(Action::Deliver(Output::Bound(result)));
+                self.listen_hop();
+            }
+        }
+    }
+
+    fn handle_search_frame(&mut self, frame: &RawFrame, status: &RxStatus) {
+        let Mode::Searching {
+            candidate, tracker, ..
+        } = self.mode
+        else {
+            return;
+        };
+        let Some(candidate) = self.models.iter().nth(candidate).copied() else {
+            return;
+        };
+
+        let packet = match TransmitterPacket::from_frame(frame) {
+            Ok(packet) if status.is_valid() => packet,
+            _ => {
+                self.actions.push(Action::Receive {
+                    deadline: self.deadline,
+                });
+                return;
+            }
+        };
+        // Whichever stored transmitter is heard first is locked on to, not only the one whose hop
+        // table is being followed, picking up its hop table from the channel it was heard on
+        let heard = self
+            .models
+            .get(packet.transmitter_id())
+            .filter(|_| packet.receiver_id() == self.receiver_id)
+            .copied()
+            .and_then(|model| {
+                let index = if model.transmitter_id() == candidate.transmitter_id() {
+                    tracker.index()
+                } else {
+                    let hop_table = &model.bind.hop_table;
+                    hop_table
+                        .iter()
+                        .position(|&channel| channel == tracker.channel())?
+                };
+                Some((model, index))
+            });
+        let Some((model, index)) = heard else {
+            self.stats.packet_foreign();
+            self.actions.push(Action::Receive {
+                deadline: self.deadline,
+            });
+            return;
+        };
+
+        self.mode = Mode::Bound {
+            bind: model.bind,
+            tracker: HopTracker::with_index(model.bind.hop_table, index, status.timestamp),
+        };
+        self.failsafe = model.failsafe;
+        self.set_channel_count(model.channel_count);
+        self.actions
+            .push(Action::Deliver(Output::Selected(model.transmitter_id())));
+        self.handle_hop_frame(frame, status);
+    }
+
+    fn handle_search_timeout(&mut self, now: Instant) {
+        let Mode::Searching {
+            candidate, sweep, ..
+        } = self.mode
+        else {
+            return;
+        };
+
+        // Move on to the next transmitter, and to the next channel once all have been tried
+        let (candidate, sweep) = if candidate + 1 < self.models.len() {
+            (candidate + 1, sweep)
+        } else {
+            (0, sweep + 1)
+        };
+        let Some(model) = self.models.iter().nth(candidate) else {
+            return;
+        };
+        self.mode = Mode::Searching {
+            candidate,
+            sweep,
+            tracker: HopTracker::with_index(model.bind.hop_table, sweep, now),
+        };
+        self.listen_hop();
+    }
+
+    fn handle_hop_frame(&mut self, frame: &RawFrame, status: &RxStatus) {
+        let Mode::Bound { bind, mut tracker } = self.mode else {
+            return;
+        };
+        let transmitter_id = bind.transmitter_id;
+        // Neither a corrupted packet nor one from another radio using the same channel can be
+        // trusted to be from the bound transmitter, so they must not throw off the hop timing.
+        // The hop is left to time out if nothing else arrives.
+        let packet = match TransmitterPacket::from_frame(frame) {
+            Ok(packet) if status.is_valid() => packet,
+            _ => {
+                self.stats.packet_corrupted(status);
+                self.actions.push(Action::Receive {
+                    deadline: self.deadline,
+                });
+                return;
+            }
+        };
+        if packet.transmitter_id() != transmitter_id || packet.receiver_id() != self.receiver_id {
+            self.stats.packet_foreign();
+            self.actions.push(Action::Receive {
+                deadline: self.deadline,
+            });
+            return;
+        }
+
+        let channel = tracker.channel();
+        self.stats.packet_received(status);
+        tracker.packet_received(status.timestamp);
+        self.mode = Mode::Bound { bind, tracker };
+
+        match packet {
+            TransmitterPacket::Sticks(packet) => {
+                self.sticks = packet.channels(self.channel_count);
+                self.last_sticks = Some(status.timestamp);
+                self.failsafe_active = false;
+                self.actions
+                    .push(Action::Deliver(Output::Channels(self.sticks)));
+                self.reply_telemetry(transmitter_id, channel, status);
+            }
+            TransmitterPacket::Failsafe(packet) => {
+                self.failsafe.update(&packet);
+                self.check_failsafe(status.timestamp);
+            }
+            _ => self.check_failsafe(status.timestamp),
+        }
+
+        self.listen_hop();
+    }
+
+    fn handle_hop_timeout(&mut self, now: Instant) {
+        let Mode::Bound { bind, mut tracker } = self.mode else {
+            return;
+        };
+        // While resyncing there is no telling how many hops the transmitter has gone through
+        if tracker.is_synced() {
+            self.stats.packet_missed(tracker.index(), now);
+        }
+        tracker.timeout(now);
+        self.mode = Mode::Bound { bind, tracker };
+
+        self.check_failsafe(now);
+        self.listen_hop();
+    }
+
+    /// Apply the failsafe positions if no sticks packet has been received for too long
+    fn check_failsafe(&mut self, now: Instant) {
+        // Only engage the failsafe once a link has been established
+        let link_lost = self
+            .last_sticks
+            .is_some_and(|last| now.micros_since(last) >= FAILSAFE_TIMEOUT_US as u64);
+        if link_lost && !self.failsafe_active {
+            self.failsafe_active = true;
+            let mut sticks = self.sticks;
+            self.failsafe.apply(&mut sticks);
+            self.actions.push(Action::Deliver(Output::Failsafe(sticks)));
+        }
+    }
+
+    /// Answer a sticks packet received on `channel` with the latest sensor readings, if any
+    fn reply_telemetry(&mut self, transmitter_id: u32, channel: u8, status: &RxStatus) {
+        let Some(mut sensors) = self.telemetry else {
+            return;
+        };
+        // Leave out the link quality if the application has already filled the packet
+        let error_rate = self.stats.snapshot(status.timestamp).error_rate_percent();
+        let _ = sensors.push(Sensor::rssi(status.rssi));
+        let _ = sensors.push(Sensor::error_rate(error_rate));
+
+        let packet = TelemetryPacket::new(transmitter_id, self.receiver_id, sensors);
+        self.actions.push(Action::Tune(channel));
+        self.actions.push(Action::Transmit(packet.to_frame()));
+    }
+
+    /// Listen on the current bind channel for a full dwell starting at `now`
+    fn listen_bind(&mut self, now: Instant) {
+        if let Mode::Binding(binder) = self.mode {
+            self.deadline = now.add_micros(BIND_LISTEN_US);
+            self.actions
+                .push(Action::Tune(rx_pll_channel(binder.channel())));
+            self.actions.push(Action::Receive {
+                deadline: self.deadline,
+            });
+        }
+    }
+
+    /// Pre-tune to the channel the next packet is expected on and listen for it
+    fn listen_hop(&mut self) {
+        if let Mode::Bound { tracker, .. } | Mode::Searching { tracker, .. } = self.mode {
+            self.deadline = tracker.deadline();
+            self.actions
+                .push(Action::Tune(rx_pll_channel(tracker.channel())));
+            self.actions.push(Action::Receive {
+                deadline: self.deadline,
+            });
+        }
+    }
+}
+
+impl Protocol for Receiver {
+    type Output = Output;
+
+    fn poll_action(&mut self) -> Option<Action<Output>> {
+        Receiver::poll_action(self)
+    }
+
+    fn handle_event(&mut self, event: Event<'_>) {
+        Receiver::handle_event(self, event)
+    }
+}
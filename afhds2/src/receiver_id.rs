@@ -0,0 +1,58 @@
+use crate::{packet::UNKNOWN_RECEIVER_ID, rng::XorShift};
+
+const FNV_OFFSET_BASIS: u32 = 0x811C_9DC5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// The ID a receiver hands to the transmitter while binding
+///
+/// A transmitter only sends to the receiver it bound with, so every receiver should have an ID of
+/// its own that stays the same for as long as it should remain bound. Deriving it with
+/// [`from_seed`](Self::from_seed) from something fixed to the hardware, such as the unique ID of
+/// the microcontroller, keeps it the same across reflashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReceiverId(u32);
+
+impl ReceiverId {
+    /// Derive an ID from the provided seed, which can be any length
+    ///
+    /// The same seed always gives the same ID, and seeds differing in any bit give unrelated IDs.
+    pub fn from_seed(seed: &[u8]) -> Self {
+        let hash = seed.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(FNV_PRIME)
+        });
+
+        // FNV leaves the low bits poorly mixed, which matters for seeds that only differ by a
+        // serial number in their last few bytes
+        let mut rng = XorShift::new(hash);
+        loop {
+            if let Some(id) = Self::from_raw(rng.next_u32()) {
+                return id;
+            }
+        }
+    }
+
+    /// Use the provided raw ID, which may have been stored after an earlier call to
+    /// [`from_seed`](Self::from_seed)
+    ///
+    /// Returns `None` for IDs that a transmitter would not accept, which are zero and
+    /// [`UNKNOWN_RECEIVER_ID`].
+    pub const fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 | UNKNOWN_RECEIVER_ID => None,
+            raw => Some(Self(raw)),
+        }
+    }
+
+    /// The raw ID, as sent in packets
+    pub const fn raw(&self) -> u32 {
+        self.0
+    }
+}
+
+impl From<ReceiverId> for u32 {
+    fn from(id: ReceiverId) -> Self {
+        id.raw()
+    }
+}
@@ -0,0 +1,22 @@
+/// A small xorshift random number generator
+///
+/// This is used wherever being repeatable matters far more than the quality of the randomness,
+/// such as deriving IDs and hop tables from a seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct XorShift(u32);
+
+impl XorShift {
+    /// Create a new generator from the provided seed
+    pub(crate) const fn new(seed: u32) -> Self {
+        // Xorshift gets stuck at zero
+        Self(if seed == 0 { 1 } else { seed })
+    }
+
+    /// The next number in the sequence
+    pub(crate) fn next_u32(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
@@ -0,0 +1,48 @@
+/// Points along the RSSI curve of the A7105 as `(raw, dBm)`, sorted by raw value
+///
+/// The A7105 reports a lower raw value for a stronger signal. This is an approximate piecewise
+/// linear fit of the RSSI curve in the datasheet, good to a few dB across the usable range.
+const RSSI_CURVE: [(u8, i16); 6] = [
+    (40, -25),
+    (80, -45),
+    (120, -65),
+    (160, -85),
+    (200, -100),
+    (240, -110),
+];
+
+/// A received signal strength measurement from the radio
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Rssi(u8);
+
+impl Rssi {
+    /// Create an [`Rssi`] from a raw ADC value read from the radio
+    pub const fn from_raw(raw: u8) -> Self {
+        Self(raw)
+    }
+
+    /// The raw ADC value read from the radio, lower values are stronger signals
+    pub const fn raw(&self) -> u8 {
+        self.0
+    }
+
+    /// The approximate signal strength in dBm
+    pub fn dbm(&self) -> i16 {
+        let raw = i16::from(self.0);
+        let (first_raw, first_dbm) = RSSI_CURVE[0];
+        if raw <= i16::from(first_raw) {
+            return first_dbm;
+        }
+
+        for window in RSSI_CURVE.windows(2) {
+            let (raw0, dbm0) = (i16::from(window[0].0), window[0].1);
+            let (raw1, dbm1) = (i16::from(window[1].0), window[1].1);
+            if raw <= raw1 {
+                return dbm0 + (raw - raw0) * (dbm1 - dbm0) / (raw1 - raw0);
+            }
+        }
+
+        RSSI_CURVE[RSSI_CURVE.len() - 1].1
+    }
+}
@@ -0,0 +1,568 @@
+//! A simulated A7105 for exercising the driver without hardware
+//!
+//! The [`SimRadio`] models the parts of the A7105 used by this crate at the level of the bytes
+//! sent over SPI: the register file, the read/write/strobe command encoding, the FIFO, the
+//! auto-clearing calibration bits and the WTR signal on GIO2. Packets are placed on the "air"
+//! with [`SimRadio::send`] and are received if the radio is listening on the right channel when
+//! they arrive, while packets transmitted by the driver are recorded for inspection.
+//!
+//! Time only moves forward when the driver delays, or when the test calls
+//! [`SimRadio::advance`], so tests are fully deterministic. Interference and hardware faults can
+//! be injected with [`SimRadio::set_faults`], using a seeded random number generator so that
+//! these tests are deterministic too.
+//!
+//! ```
+//! use afhds2::{sim::SimRadio, time::Instant, Afhds2, RawFrame};
+//!
+//! let sim = SimRadio::new();
+//! let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+//! radio.configure_radio(sim.delay()).unwrap();
+//! radio.set_channel(0x50).unwrap();
+//!
+//! sim.send(Instant::from_micros(60_000), 0x51, RawFrame::default());
+//! let (frame, status) = radio.receive(sim.delay(), &sim, 20_000).unwrap();
+//! assert_eq!(frame, RawFrame::default());
+//! assert!(status.is_valid());
+//! ```
+
+extern crate std;
+
+use core::{cell::RefCell, convert::Infallible};
+use std::vec::Vec;
+
+use embedded_hal::{delay::DelayUs, digital, spi};
+
+use crate::{
+    rng::XorShift,
+    time::{Clock, Instant},
+    RawFrame, PACKET_SIZE,
+};
+
+/// How long the simulated radio takes to transmit a packet
+pub const TX_DURATION_US: u32 = 1_000;
+
+/// How long the simulated radio takes to complete each calibration
+pub const CALIBRATION_DURATION_US: u32 = 20;
+
+/// The RSSI reported for received packets until changed with [`SimRadio::set_rssi`]
+pub const DEFAULT_RSSI: u8 = 80;
+
+/// The IF filter bank reported by the simulated calibration
+pub const IF_FILTER_BANK: u8 = 0x05;
+
+/// The VCO bank reported by the simulated calibration
+pub const VCO_BANK: u8 = 0x02;
+
+/// The seed used for the fault injection until changed with [`SimRadio::set_seed`]
+pub const DEFAULT_SEED: u32 = 0x2545_f491;
+
+const REGISTER_COUNT: usize = 0x33;
+const ID_LENGTH: usize = 4;
+
+const REG_MODE: u8 = 0x00;
+const REG_CALIBRATION_CONTROL: u8 = 0x02;
+const REG_FIFO_DATA: u8 = 0x05;
+const REG_ID_DATA: u8 = 0x06;
+const REG_GPIO2_PIN_CONTROL: u8 = 0x0c;
+const REG_PLL1: u8 = 0x0f;
+const REG_RSSI: u8 = 0x1d;
+const REG_IF_CALIBRATION_RESULT: u8 = 0x22;
+const REG_VCO_CURRENT_CALIBRATION: u8 = 0x24;
+const REG_VCO_BANK_CALIBRATION: u8 = 0x25;
+
+const COMMAND_STROBE: u8 = 0x80;
+const COMMAND_READ: u8 = 0x40;
+const ADDRESS_MASK: u8 = 0x3f;
+
+const STROBE_SLEEP: u8 = 0x80;
+const STROBE_IDLE: u8 = 0x90;
+const STROBE_STANDBY: u8 = 0xa0;
+const STROBE_PLL: u8 = 0xb0;
+const STROBE_RX: u8 = 0xc0;
+const STROBE_TX: u8 = 0xd0;
+const STROBE_TX_FIFO_RESET: u8 = 0xe0;
+const STROBE_RX_FIFO_RESET: u8 = 0xf0;
+
+/// The faults injected by a [`SimRadio`], by default none
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Faults {
+    /// The percentage of packets on air that are lost rather than received
+    pub packet_loss_percent: u8,
+    /// The number of random bits flipped in every packet received, without the radio noticing
+    pub bit_flips: u8,
+    /// Flag every packet received as failing its CRC check
+    pub crc_error: bool,
+    /// Flag every packet received as having uncorrectable FEC errors
+    pub fec_error: bool,
+    /// Never clear the auto-clearing calibration bits
+    pub stuck_calibration: bool,
+    /// Fail every SPI transaction after this many more have succeeded
+    pub spi_error_after: Option<u32>,
+}
+
+/// The error reported by a [`SimSpi`] when [`Faults::spi_error_after`] is reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimSpiError;
+
+impl spi::Error for SimSpiError {
+    fn kind(&self) -> spi::ErrorKind {
+        spi::ErrorKind::Other
+    }
+}
+
+/// A packet sent over the air by the driver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transmission {
+    /// When the transmission started
+    pub at: Instant,
+    /// The PLL channel the packet was sent on
+    pub channel: u8,
+    /// The packet that was sent
+    pub frame: RawFrame,
+}
+
+/// A packet waiting to be sent over the air to the simulated radio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AirFrame {
+    at: Instant,
+    channel: u8,
+    frame: RawFrame,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RadioState {
+    Standby,
+    Rx { since: Instant },
+    Tx { until: Instant },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Write(u8),
+    Read(u8),
+    Strobe,
+}
+
+struct State {
+    now: Instant,
+    registers: [u8; REGISTER_COUNT],
+    id: [u8; ID_LENGTH],
+    radio: RadioState,
+    calibration_done: Instant,
+    rx_fifo: [u8; PACKET_SIZE],
+    tx_fifo: [u8; PACKET_SIZE],
+    fifo_pointer: usize,
+    rssi: u8,
+    interference: [Option<u8>; 256],
+    rx_flags: u8,
+    faults: Faults,
+    rng: XorShift,
+    air: Vec<AirFrame>,
+    transmissions: Vec<Transmission>,
+}
+
+impl State {
+    fn new() -> Self {
+        Self {
+            now: Instant::from_micros(0),
+            registers: [0; REGISTER_COUNT],
+            id: [0; ID_LENGTH],
+            radio: RadioState::Standby,
+            calibration_done: Instant::from_micros(0),
+            rx_fifo: [0; PACKET_SIZE],
+            tx_fifo: [0; PACKET_SIZE],
+            fifo_pointer: 0,
+            rssi: DEFAULT_RSSI,
+            interference: [None; 256],
+            rx_flags: 0,
+            faults: Faults::default(),
+            rng: XorShift::new(DEFAULT_SEED),
+            air: Vec::new(),
+            transmissions: Vec::new(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.registers = [0; REGISTER_COUNT];
+        self.id = [0; ID_LENGTH];
+        self.radio = RadioState::Standby;
+        self.calibration_done = self.now;
+        self.fifo_pointer = 0;
+        self.rx_flags = 0;
+    }
+
+    /// Bring the radio up to date with the current time
+    fn update(&mut self) {
+        match self.radio {
+            RadioState::Rx { since } => {
+                // The radio is tuned one channel below the one it receives on
+                let channel = self.registers[REG_PLL1 as usize].wrapping_add(1);
+                let now = self.now;
+                let received = self
+                    .air
+                    .iter()
+                    .filter(|air| air.channel == channel && air.at >= since && air.at <= now)
+                    .min_by_key(|air| air.at)
+                    .copied();
+                if let Some(air) = received {
+                    // Lost packets are missed entirely, the radio keeps on listening
+                    if self.rng.next_u32() % 100 >= self.faults.packet_loss_percent as u32 {
+                        self.receive(&air);
+                    }
+                }
+            }
+            RadioState::Tx { until } if until <= self.now => self.radio = RadioState::Standby,
+            _ => {}
+        }
+
+        // Anything that has finished arriving has either been received or missed
+        let now = self.now;
+        self.air.retain(|air| air.at > now);
+
+        if self.calibration_done <= self.now && !self.faults.stuck_calibration {
+            self.registers[REG_CALIBRATION_CONTROL as usize] = 0;
+        }
+    }
+
+    /// Finish receiving a packet, applying any faults to it
+    fn receive(&mut self, air: &AirFrame) {
+        self.rx_fifo = *air.frame.as_bytes();
+        for _ in 0..self.faults.bit_flips {
+            let bit = self.rng.next_u32() as usize % (PACKET_SIZE * 8);
+            self.rx_fifo[bit / 8] ^= 1 << (bit % 8);
+        }
+        self.rx_flags = ((self.faults.fec_error as u8) << 6) | ((self.faults.crc_error as u8) << 5);
+        self.radio = RadioState::Standby;
+    }
+
+    fn wtr(&self) -> bool {
+        let control = self.registers[REG_GPIO2_PIN_CONTROL as usize];
+        let is_wtr = (control >> 2) & 0x0f == 0;
+        let output_enabled = control & 0x01 != 0;
+        let inverted = control & 0x02 != 0;
+        let busy = !matches!(self.radio, RadioState::Standby);
+        is_wtr && output_enabled && (busy != inverted)
+    }
+
+    fn strobe(&mut self, strobe: u8) {
+        match strobe & 0xf0 {
+            STROBE_SLEEP | STROBE_IDLE | STROBE_STANDBY | STROBE_PLL => {
+                self.radio = RadioState::Standby
+            }
+            STROBE_RX => {
+                self.rx_flags = 0;
+                self.radio = RadioState::Rx { since: self.now };
+            }
+            STROBE_TX => {
+                self.transmissions.push(Transmission {
+                    at: self.now,
+                    channel: self.registers[REG_PLL1 as usize],
+                    frame: RawFrame::new(self.tx_fifo),
+                });
+                self.radio = RadioState::Tx {
+                    until: self.now.add_micros(TX_DURATION_US),
+                };
+            }
+            STROBE_TX_FIFO_RESET | STROBE_RX_FIFO_RESET => self.fifo_pointer = 0,
+            _ => {}
+        }
+    }
+
+    fn write(&mut self, address: u8, offset: usize, value: u8) {
+        match address {
+            REG_MODE => self.reset(),
+            REG_FIFO_DATA => {
+                if let Some(byte) = self.tx_fifo.get_mut(self.fifo_pointer) {
+                    *byte = value;
+                    self.fifo_pointer += 1;
+                }
+            }
+            REG_ID_DATA => {
+                if let Some(byte) = self.id.get_mut(offset) {
+                    *byte = value;
+                }
+            }
+            REG_CALIBRATION_CONTROL => {
+                self.registers[address as usize] = value;
+                self.calibration_done = self.now.add_micros(CALIBRATION_DURATION_US);
+            }
+            _ => {
+                if let Some(register) = self.registers.get_mut(address as usize) {
+                    *register = value;
+                }
+            }
+        }
+    }
+
+    fn read(&mut self, address: u8, offset: usize) -> u8 {
+        match address {
+            REG_MODE => {
+                let busy = !matches!(self.radio, RadioState::Standby);
+                let tx = matches!(self.radio, RadioState::Tx { .. });
+                self.rx_flags | (1 << 4) | (1 << 3) | (1 << 2) | ((tx as u8) << 1) | busy as u8
+            }
+            REG_FIFO_DATA => {
+                let value = self.rx_fifo.get(self.fifo_pointer).copied().unwrap_or(0);
+                self.fifo_pointer += 1;
+                value
+            }
+            REG_ID_DATA => self.id.get(offset).copied().unwrap_or(0),
+            REG_RSSI => match self.radio {
+                RadioState::Rx { .. } => self.interference
+                    [self.registers[REG_PLL1 as usize] as usize]
+                    .unwrap_or(self.rssi),
+                _ => self.rssi,
+            },
+            REG_IF_CALIBRATION_RESULT => IF_FILTER_BANK,
+            REG_VCO_CURRENT_CALIBRATION => self.registers[address as usize] & 0x0f,
+            REG_VCO_BANK_CALIBRATION => VCO_BANK,
+            _ => self.registers.get(address as usize).copied().unwrap_or(0),
+        }
+    }
+
+    /// Handle a single SPI transaction, made up of a command byte followed by any data
+    fn transaction(&mut self, operations: &mut [spi::Operation<'_, u8>]) {
+        let mut access = None;
+        let mut offset = 0;
+
+        let mut byte = |state: &mut Self, written: Option<u8>| -> u8 {
+            match access {
+                None => {
+                    let command = written.unwrap_or(0);
+                    if command & COMMAND_STROBE != 0 {
+                        state.strobe(command);
+                        access = Some(Access::Strobe);
+                    } else if command & COMMAND_READ != 0 {
+                        access = Some(Access::Read(command & ADDRESS_MASK));
+                    } else {
+                        access = Some(Access::Write(command & ADDRESS_MASK));
+                    }
+                    0
+                }
+                Some(Access::Write(address)) => {
+                    state.write(address, offset, written.unwrap_or(0));
+                    offset += 1;
+                    0
+                }
+                Some(Access::Read(address)) => {
+                    let value = state.read(address, offset);
+                    offset += 1;
+                    value
+                }
+                Some(Access::Strobe) => 0,
+            }
+        };
+
+        for operation in operations {
+            match operation {
+                spi::Operation::Read(buf) => {
+                    for b in buf.iter_mut() {
+                        *b = byte(self, None);
+                    }
+                }
+                spi::Operation::Write(buf) => {
+                    for b in buf.iter() {
+                        byte(self, Some(*b));
+                    }
+                }
+                spi::Operation::Transfer(read, write) => {
+                    for i in 0..read.len().max(write.len()) {
+                        let value = byte(self, write.get(i).copied());
+                        if let Some(b) = read.get_mut(i) {
+                            *b = value;
+                        }
+                    }
+                }
+                spi::Operation::TransferInPlace(buf) => {
+                    for b in buf.iter_mut() {
+                        *b = byte(self, Some(*b));
+                    }
+                }
+                spi::Operation::DelayUs(us) => {
+                    self.now = self.now.add_micros(*us);
+                    self.update();
+                }
+            }
+        }
+    }
+}
+
+/// A simulated A7105 radio and the air around it
+///
+/// The radio is shared between the [`SimSpi`], [`SimPin`] and [`SimDelay`] handed to the
+/// driver, and implements [`Clock`] so it can be used as the driver's source of time.
+pub struct SimRadio {
+    state: RefCell<State>,
+}
+
+impl SimRadio {
+    /// Create a new [`SimRadio`] in its reset state, at time zero
+    pub fn new() -> Self {
+        Self {
+            state: RefCell::new(State::new()),
+        }
+    }
+
+    /// The SPI bus connected to the simulated radio
+    pub fn spi(&self) -> SimSpi<'_> {
+        SimSpi { radio: self }
+    }
+
+    /// The GPIO pin connected to GIO2 of the simulated radio
+    pub fn gpio(&self) -> SimPin<'_> {
+        SimPin { radio: self }
+    }
+
+    /// A delay that advances the simulated time
+    pub fn delay(&self) -> SimDelay<'_> {
+        SimDelay { radio: self }
+    }
+
+    /// The current simulated time
+    pub fn now(&self) -> Instant {
+        self.state.borrow().now
+    }
+
+    /// Move the simulated time forward
+    pub fn advance(&self, micros: u32) {
+        let mut state = self.state.borrow_mut();
+        state.now = state.now.add_micros(micros);
+        state.update();
+    }
+
+    /// Send a packet over the air on the provided channel, finishing arriving at `at`
+    ///
+    /// The packet is received if the radio is in RX mode, tuned one channel below `channel`, from
+    /// before `at` until at least `at`.
+    pub fn send(&self, at: Instant, channel: u8, frame: RawFrame) {
+        self.state
+            .borrow_mut()
+            .air
+            .push(AirFrame { at, channel, frame });
+    }
+
+    /// Every packet transmitted by the radio so far
+    pub fn transmissions(&self) -> Vec<Transmission> {
+        self.state.borrow().transmissions.clone()
+    }
+
+    /// Inject the provided faults from now on
+    pub fn set_faults(&self, faults: Faults) {
+        self.state.borrow_mut().faults = faults;
+    }
+
+    /// The faults currently being injected
+    pub fn faults(&self) -> Faults {
+        self.state.borrow().faults
+    }
+
+    /// Seed the random number generator used for the fault injection
+    pub fn set_seed(&self, seed: u32) {
+        self.state.borrow_mut().rng = XorShift::new(seed);
+    }
+
+    /// Set the RSSI reported by the radio
+    pub fn set_rssi(&self, rssi: u8) {
+        self.state.borrow_mut().rssi = rssi;
+    }
+
+    /// Set the RSSI measured while listening with the PLL tuned to `channel`, as if something
+    /// else was transmitting there
+    pub fn set_interference(&self, channel: u8, rssi: u8) {
+        self.state.borrow_mut().interference[channel as usize] = Some(rssi);
+    }
+
+    /// The value last written to the register at the provided address
+    pub fn register(&self, address: u8) -> u8 {
+        let state = self.state.borrow();
+        state.registers.get(address as usize).copied().unwrap_or(0)
+    }
+
+    /// The ID last written to the radio
+    pub fn id(&self) -> u32 {
+        u32::from_be_bytes(self.state.borrow().id)
+    }
+
+    /// The channel the PLL is currently tuned to
+    pub fn channel(&self) -> u8 {
+        self.register(REG_PLL1)
+    }
+
+    /// Returns true while the radio is in RX mode waiting for a packet
+    pub fn is_receiving(&self) -> bool {
+        let mut state = self.state.borrow_mut();
+        state.update();
+        matches!(state.radio, RadioState::Rx { .. })
+    }
+}
+
+impl Default for SimRadio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SimRadio {
+    fn now(&self) -> Instant {
+        SimRadio::now(self)
+    }
+}
+
+/// The SPI bus connected to a [`SimRadio`]
+pub struct SimSpi<'a> {
+    radio: &'a SimRadio,
+}
+
+impl spi::ErrorType for SimSpi<'_> {
+    type Error = SimSpiError;
+}
+
+impl spi::SpiDevice for SimSpi<'_> {
+    fn transaction(
+        &mut self,
+        operations: &mut [spi::Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        let mut state = self.radio.state.borrow_mut();
+        match state.faults.spi_error_after {
+            Some(0) => return Err(SimSpiError),
+            Some(remaining) => state.faults.spi_error_after = Some(remaining - 1),
+            None => {}
+        }
+
+        state.update();
+        state.transaction(operations);
+        Ok(())
+    }
+}
+
+/// The GPIO pin connected to GIO2 of a [`SimRadio`]
+pub struct SimPin<'a> {
+    radio: &'a SimRadio,
+}
+
+impl digital::ErrorType for SimPin<'_> {
+    type Error = Infallible;
+}
+
+impl digital::InputPin for SimPin<'_> {
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        let mut state = self.radio.state.borrow_mut();
+        state.update();
+        Ok(state.wtr())
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+/// A delay that advances the time of a [`SimRadio`]
+pub struct SimDelay<'a> {
+    radio: &'a SimRadio,
+}
+
+impl DelayUs for SimDelay<'_> {
+    fn delay_us(&mut self, us: u32) {
+        self.radio.advance(us);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::Rssi;
+
+/// The highest channel the A7105 can be tuned to
+pub const MAX_SCAN_CHANNEL: u8 = 0xA0;
+
+/// The number of channels covered by a [`SpectrumReport`]
+pub const NUM_SCAN_CHANNELS: usize = MAX_SCAN_CHANNEL as usize + 1;
+
+/// How many RSSI samples are taken on each channel during a spectrum scan
+pub const SCAN_SAMPLES: u32 = 8;
+
+/// The signal strength above which a channel is considered busy by [`SpectrumReport::is_busy`]
+pub const BUSY_THRESHOLD_DBM: i16 = -85;
+
+/// The signal strength measured on a single channel during a spectrum scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChannelOccupancy {
+    /// The strongest signal sampled on the channel
+    pub peak: Rssi,
+    /// The average of every sample on the channel
+    pub average: Rssi,
+}
+
+impl ChannelOccupancy {
+    /// Summarize the RSSI samples taken on a single channel
+    pub(crate) fn from_samples(samples: &[Rssi]) -> Self {
+        // The A7105 reports lower raw values for stronger signals
+        let peak = samples.iter().min().copied().unwrap_or_default();
+        let total: u32 = samples.iter().map(|rssi| u32::from(rssi.raw())).sum();
+        let average = total / (samples.len() as u32).max(1);
+        Self {
+            peak,
+            average: Rssi::from_raw(average as u8),
+        }
+    }
+}
+
+/// The signal strength on every channel the A7105 can be tuned to, from
+/// [`Afhds2::spectrum_scan`](crate::Afhds2::spectrum_scan)
+///
+/// Channels are numbered as written to the PLL, so a transmitter sending on channel `n` shows up
+/// on channel `n - 1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SpectrumReport {
+    channels: [ChannelOccupancy; NUM_SCAN_CHANNELS],
+}
+
+impl SpectrumReport {
+    pub(crate) const fn new() -> Self {
+        Self {
+            channels: [ChannelOccupancy {
+                peak: Rssi::from_raw(0),
+                average: Rssi::from_raw(0),
+            }; NUM_SCAN_CHANNELS],
+        }
+    }
+
+    pub(crate) fn set(&mut self, channel: u8, occupancy: ChannelOccupancy) {
+        self.channels[channel as usize] = occupancy;
+    }
+
+    /// The signal strength measured on the provided channel
+    pub fn channel(&self, channel: u8) -> Option<&ChannelOccupancy> {
+        self.channels.get(channel as usize)
+    }
+
+    /// The signal strength measured on every channel, in order
+    pub fn channels(&self) -> impl Iterator<Item = (u8, &ChannelOccupancy)> {
+        self.channels
+            .iter()
+            .enumerate()
+            .map(|(channel, occupancy)| (channel as u8, occupancy))
+    }
+
+    /// Returns true if the strongest signal on the provided channel was above
+    /// [`BUSY_THRESHOLD_DBM`]
+    pub fn is_busy(&self, channel: u8) -> bool {
+        self.channel(channel)
+            .is_some_and(|occupancy| occupancy.peak.dbm() > BUSY_THRESHOLD_DBM)
+    }
+
+    /// The number of channels that are [busy](Self::is_busy)
+    pub fn busy_channels(&self) -> usize {
+        (0..=MAX_SCAN_CHANNEL)
+            .filter(|&channel| self.is_busy(channel))
+            .count()
+    }
+
+    /// The channel with the strongest average signal
+    pub fn busiest(&self) -> u8 {
+        self.channels()
+            .min_by_key(|(_, occupancy)| occupancy.average)
+            .map_or(0, |(channel, _)| channel)
+    }
+}
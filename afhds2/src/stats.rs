@@ -0,0 +1,169 @@
+//! Statistics on how well the link to the transmitter is working
+
+use crate::{hopping::HOP_PERIOD_US, packet::NUM_HOP_CHANNELS, time::Instant, Rssi, RxStatus};
+
+/// How long each window that packet rates are measured over lasts
+const WINDOW_US: u64 = 1_000_000;
+
+/// The weight of each new measurement in the RSSI average, as a power of two
+const RSSI_SMOOTHING_SHIFT: u32 = 3;
+
+/// The number of fractional bits kept in the RSSI average
+const RSSI_FRACTION_BITS: u32 = 4;
+
+/// Counters describing the link to the transmitter, updated by the [`Receiver`](crate::Receiver)
+/// as packets arrive or are missed
+///
+/// A hop is counted as missed when no intact packet arrived on it, whether nothing was heard at
+/// all or what was heard failed its CRC or FEC checks. Packets from other transmitters, or meant
+/// for other receivers, are counted separately and do not affect any of the other counters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LinkStats {
+    window_start: Instant,
+    window_received: u32,
+    received_per_second: u32,
+    expected_per_second: u32,
+    crc_errors: u32,
+    fec_errors: u32,
+    foreign_packets: u32,
+    miss_streak: u32,
+    longest_miss_streak: u32,
+    channel_losses: [u32; NUM_HOP_CHANNELS],
+    last_packet: Option<Instant>,
+    /// The RSSI average with [`RSSI_FRACTION_BITS`] fractional bits
+    rssi_average: Option<u16>,
+}
+
+impl LinkStats {
+    /// Create a new [`LinkStats`], with the first measurement window starting at `now`
+    pub const fn new(now: Instant) -> Self {
+        Self {
+            window_start: now,
+            window_received: 0,
+            received_per_second: 0,
+            expected_per_second: 0,
+            crc_errors: 0,
+            fec_errors: 0,
+            foreign_packets: 0,
+            miss_streak: 0,
+            longest_miss_streak: 0,
+            channel_losses: [0; NUM_HOP_CHANNELS],
+            last_packet: None,
+            rssi_average: None,
+        }
+    }
+
+    /// Record an intact packet from the transmitter
+    pub fn packet_received(&mut self, status: &RxStatus) {
+        self.roll_window(status.timestamp);
+        self.window_received += 1;
+        self.miss_streak = 0;
+        self.last_packet = Some(status.timestamp);
+
+        let rssi = u16::from(status.rssi.raw()) << RSSI_FRACTION_BITS;
+        self.rssi_average = Some(match self.rssi_average {
+            Some(average) => {
+                average - (average >> RSSI_SMOOTHING_SHIFT) + (rssi >> RSSI_SMOOTHING_SHIFT)
+            }
+            None => rssi,
+        });
+    }
+
+    /// Record a packet that failed its CRC or FEC checks
+    ///
+    /// The hop it was received on still counts as missed if nothing else arrives before its
+    /// deadline, which is recorded by [`packet_missed`](Self::packet_missed).
+    pub fn packet_corrupted(&mut self, status: &RxStatus) {
+        if status.crc_error {
+            self.crc_errors += 1;
+        }
+        if status.fec_error {
+            self.fec_errors += 1;
+        }
+    }
+
+    /// Record that nothing was received on the hop at `index` of the hop table
+    pub fn packet_missed(&mut self, index: usize, now: Instant) {
+        self.roll_window(now);
+        self.miss_streak += 1;
+        self.longest_miss_streak = self.longest_miss_streak.max(self.miss_streak);
+        self.channel_losses[index % NUM_HOP_CHANNELS] += 1;
+    }
+
+    /// Record an intact packet that was not sent to this receiver by the bound transmitter
+    pub fn packet_foreign(&mut self) {
+        self.foreign_packets += 1;
+    }
+
+    /// Take a copy of the current statistics
+    pub fn snapshot(&self, now: Instant) -> LinkSnapshot {
+        LinkSnapshot {
+            received_per_second: self.received_per_second,
+            expected_per_second: self.expected_per_second,
+            crc_errors: self.crc_errors,
+            fec_errors: self.fec_errors,
+            foreign_packets: self.foreign_packets,
+            miss_streak: self.miss_streak,
+            longest_miss_streak: self.longest_miss_streak,
+            channel_losses: self.channel_losses,
+            last_packet_age_us: self.last_packet.map(|last| now.micros_since(last)),
+            average_rssi: self
+                .rssi_average
+                .map(|average| Rssi::from_raw((average >> RSSI_FRACTION_BITS) as u8)),
+        }
+    }
+
+    /// Start a new measurement window once the current one is over
+    fn roll_window(&mut self, now: Instant) {
+        let elapsed = now.micros_since(self.window_start);
+        if elapsed < WINDOW_US {
+            return;
+        }
+
+        // Scale to a full second, in case nothing happened for longer than a window
+        let expected = elapsed / HOP_PERIOD_US as u64;
+        self.expected_per_second = (expected * WINDOW_US / elapsed) as u32;
+        self.received_per_second = (self.window_received as u64 * WINDOW_US / elapsed) as u32;
+        self.window_start = now;
+        self.window_received = 0;
+    }
+}
+
+/// A copy of the [`LinkStats`] at a single point in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LinkSnapshot {
+    /// How many intact packets were received over the last full second
+    pub received_per_second: u32,
+    /// How many packets the transmitter sent over the last full second
+    pub expected_per_second: u32,
+    /// How many packets have failed their CRC check
+    pub crc_errors: u32,
+    /// How many packets have failed their FEC check
+    pub fec_errors: u32,
+    /// How many packets have been ignored for being from another transmitter, or for another
+    /// receiver
+    pub foreign_packets: u32,
+    /// How many hops in a row have been missed, up to now
+    pub miss_streak: u32,
+    /// The most hops in a row that have ever been missed
+    pub longest_miss_streak: u32,
+    /// How many hops have been missed on each position of the hop table
+    pub channel_losses: [u32; NUM_HOP_CHANNELS],
+    /// How long ago the last intact packet was received, if one ever has been
+    pub last_packet_age_us: Option<u64>,
+    /// The average signal strength of intact packets, if any have been received
+    pub average_rssi: Option<Rssi>,
+}
+
+impl LinkSnapshot {
+    /// The percentage of packets lost over the last full second, zero until a second has passed
+    pub fn error_rate_percent(&self) -> u8 {
+        if self.expected_per_second == 0 {
+            return 0;
+        }
+        let received = self.received_per_second.min(self.expected_per_second);
+        (100 - received * 100 / self.expected_per_second) as u8
+    }
+}
@@ -0,0 +1,37 @@
+use crate::{time::Instant, Error, Rssi};
+
+/// The state of the radio when a packet was received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RxStatus {
+    /// The packet failed its CRC check
+    pub crc_error: bool,
+    /// The packet had errors that could not be corrected by FEC
+    pub fec_error: bool,
+    /// The radio had finished receiving when the status was read
+    pub data_ready: bool,
+    /// When the packet was received
+    pub timestamp: Instant,
+    /// The channel the packet was received on
+    pub channel: u8,
+    /// The signal strength measured while the packet was received
+    pub rssi: Rssi,
+}
+
+impl RxStatus {
+    /// Returns true if the packet passed both its CRC and FEC checks
+    pub const fn is_valid(&self) -> bool {
+        !(self.crc_error || self.fec_error)
+    }
+
+    /// Convert any CRC or FEC failure into the matching [`Error`]
+    pub const fn check<SpiE, PinE>(&self) -> Result<(), Error<SpiE, PinE>> {
+        if self.crc_error {
+            Err(Error::CrcError)
+        } else if self.fec_error {
+            Err(Error::FecError)
+        } else {
+            Ok(())
+        }
+    }
+}
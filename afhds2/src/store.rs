@@ -0,0 +1,593 @@
+//! A log-structured configuration store for flash memory
+//!
+//! Settings are saved as small, versioned [`Record`]s appended one after another to a sector of
+//! flash, so that changing a setting only writes a few more bytes rather than erasing the whole
+//! sector. Two sectors are used in turn: once the active one is full, the latest copy of every
+//! record is moved over to the other, which then becomes the active one.
+//!
+//! Every record carries a CRC, so a record torn by losing power part way through writing it is
+//! ignored when the store is next opened. The header marking a sector as active is only written
+//! once every record has been moved over to it, so losing power while doing so leaves the old
+//! sector in use.
+//!
+//! ```
+//! use afhds2::{
+//!     bind::BindResult,
+//!     store::{ConfigStore, MemoryStorage},
+//!     ModelMemory,
+//! };
+//!
+//! let mut store = ConfigStore::open(MemoryStorage::<1024>::new()).unwrap();
+//! let mut models = ModelMemory::new();
+//! models
+//!     .add(BindResult {
+//!         transmitter_id: 0x1234_5678,
+//!         hop_table: [0x14; 16],
+//!         options: [0xFF; 10],
+//!     })
+//!     .unwrap();
+//! store.save(&models).unwrap();
+//!
+//! // Power cycle
+//! let mut store = ConfigStore::open(store.into_storage()).unwrap();
+//! assert_eq!(store.load::<ModelMemory>(), Ok(Some(models)));
+//! ```
+
+/// The largest payload a single record can have, in bytes
+pub const MAX_RECORD_SIZE: usize = u8::MAX as usize;
+
+/// The number of distinct record keys, every [`Record::KEY`] has to be below this
+pub const MAX_KEYS: usize = 32;
+
+/// Keys below this are used by the records defined in this crate, applications should start
+/// numbering their own records here
+pub const FIRST_APPLICATION_KEY: u8 = 8;
+
+/// The largest [`Storage::WRITE_SIZE`] supported
+pub const MAX_WRITE_SIZE: usize = 32;
+
+/// The version of the layout of the sectors themselves, sectors written with any other version
+/// are erased when the store is opened
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Marks the start of an active sector
+const MAGIC: u16 = 0xC0F1;
+
+/// The magic, format version, a reserved byte and the generation
+const HEADER_SIZE: usize = 8;
+
+/// The key, version, payload length and a reserved byte
+const RECORD_HEADER_SIZE: usize = 4;
+
+const CRC_SIZE: usize = 4;
+
+/// Big enough for any record, including its padding
+const RECORD_BUFFER_SIZE: usize = RECORD_HEADER_SIZE + MAX_RECORD_SIZE + CRC_SIZE + MAX_WRITE_SIZE;
+
+/// The version written for a removed record
+const TOMBSTONE: u8 = 0xFF;
+
+/// The value of erased flash
+const ERASED: u8 = 0xFF;
+
+/// Flash memory, or anything that behaves like it, with two sectors set aside for a
+/// [`ConfigStore`]
+///
+/// Offsets are from the start of the first sector, with the second sector following straight on
+/// from it.
+pub trait Storage {
+    /// The error reported by the storage
+    type Error;
+
+    /// The size of each of the two sectors, in bytes
+    const SECTOR_SIZE: u32;
+
+    /// Every write is a multiple of this many bytes, starting at an offset that is also a
+    /// multiple of it, which can be no more than [`MAX_WRITE_SIZE`]
+    const WRITE_SIZE: u32;
+
+    /// Read `bytes.len()` bytes starting at `offset`
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Write `bytes` starting at `offset`, which will only ever be to bytes erased since they
+    /// were last written
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    /// Erase the sector at the provided index, either 0 or 1
+    fn erase(&mut self, sector: u8) -> Result<(), Self::Error>;
+}
+
+/// A value that can be saved to a [`ConfigStore`]
+///
+/// The encoding is up to the implementation. Whenever it changes, [`VERSION`](Self::VERSION)
+/// should be increased and [`decode`](Self::decode) taught to migrate the older versions, which
+/// are rewritten in the current version the first time they are loaded.
+pub trait Record: Sized {
+    /// Identifies the record in the store, which has to be below [`MAX_KEYS`]
+    const KEY: u8;
+
+    /// The version of the encoding written by [`encode`](Self::encode), which has to be below 255
+    const VERSION: u8;
+
+    /// Encode the record into `bytes`, returning the number of bytes used
+    fn encode(&self, bytes: &mut [u8; MAX_RECORD_SIZE]) -> usize;
+
+    /// Decode a record written with the provided version of the encoding, which is never newer
+    /// than [`VERSION`](Self::VERSION)
+    ///
+    /// Returns `None` if the record cannot be decoded, in which case it is treated as missing.
+    fn decode(version: u8, bytes: &[u8]) -> Option<Self>;
+}
+
+/// The errors that can be reported by a [`ConfigStore`]
+///
+/// `E` is the error type of the [`Storage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StoreError<E> {
+    /// The underlying storage reported an error
+    Storage(E),
+    /// The record key is not below [`MAX_KEYS`]
+    InvalidKey,
+    /// The record version is 255, which marks a removed record
+    InvalidVersion,
+    /// Even after moving to the other sector there is no room for the record
+    Full,
+}
+
+/// An intact record read back from storage
+struct StoredRecord<'a> {
+    key: u8,
+    version: u8,
+    payload: &'a [u8],
+}
+
+/// A key/value store for [`Record`]s, kept in two sectors of [`Storage`]
+#[derive(Debug)]
+pub struct ConfigStore<S> {
+    storage: S,
+    sector: u8,
+    generation: u32,
+    /// Where the next record will be written, from the start of the active sector
+    end: u32,
+    /// Where the latest copy of each record is, from the start of the active sector
+    index: [Option<u32>; MAX_KEYS],
+}
+
+impl<S: Storage> ConfigStore<S> {
+    /// Fails to build for storage with writes too large to pad records out to
+    const WRITE_SIZE_SUPPORTED: () = assert!(S::WRITE_SIZE as usize <= MAX_WRITE_SIZE);
+
+    /// Open the store kept in `storage`, erasing it if it has never been used
+    pub fn open(mut storage: S) -> Result<Self, StoreError<S::Error>> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::WRITE_SIZE_SUPPORTED;
+
+        let first = read_header(&mut storage, 0)?;
+        let second = read_header(&mut storage, 1)?;
+        // Both sectors are valid if power was lost after moving to a sector, but before the
+        // old one was erased
+        let active = match (first, second) {
+            (Some(first), Some(second)) if second > first => Some((1, second)),
+            (Some(first), _) => Some((0, first)),
+            (None, Some(second)) => Some((1, second)),
+            (None, None) => None,
+        };
+
+        let mut store = Self {
+            storage,
+            sector: 0,
+            generation: 0,
+            end: Self::data_start(),
+            index: [None; MAX_KEYS],
+        };
+        match active {
+            Some((sector, generation)) => {
+                store.sector = sector;
+                store.generation = generation;
+                store.scan()?;
+            }
+            None => {
+                store.storage.erase(0).map_err(StoreError::Storage)?;
+                store.write_header(0, 0)?;
+            }
+        }
+        Ok(store)
+    }
+
+    /// Give back the underlying storage
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    /// Load the latest copy of a record, if there is one
+    ///
+    /// A record saved with an older version is migrated and saved again in the current version.
+    /// A record saved with a newer version, by newer firmware, is treated as missing.
+    pub fn load<R: Record>(&mut self) -> Result<Option<R>, StoreError<S::Error>> {
+        let Some(offset) = self.index[key_index(R::KEY)?] else {
+            return Ok(None);
+        };
+
+        let mut buffer = [0; RECORD_BUFFER_SIZE];
+        let Some(stored) = self.read_record(offset, &mut buffer)? else {
+            return Ok(None);
+        };
+        if stored.version > R::VERSION {
+            return Ok(None);
+        }
+        let Some(record) = R::decode(stored.version, stored.payload) else {
+            return Ok(None);
+        };
+
+        if stored.version < R::VERSION {
+            self.save(&record)?;
+        }
+        Ok(Some(record))
+    }
+
+    /// Save a record, replacing any earlier copy
+    ///
+    /// Nothing is written if the record is unchanged.
+    pub fn save<R: Record>(&mut self, record: &R) -> Result<(), StoreError<S::Error>> {
+        // Otherwise the record would read back as removed
+        if R::VERSION == TOMBSTONE {
+            return Err(StoreError::InvalidVersion);
+        }
+        let mut payload = [0; MAX_RECORD_SIZE];
+        let len = record.encode(&mut payload);
+        self.write_record(R::KEY, R::VERSION, &payload[..len])
+    }
+
+    /// Remove a record, so that it is no longer loaded
+    pub fn remove<R: Record>(&mut self) -> Result<(), StoreError<S::Error>> {
+        if self.index[key_index(R::KEY)?].is_none() {
+            return Ok(());
+        }
+        self.write_record(R::KEY, TOMBSTONE, &[])
+    }
+
+    /// The sector currently in use, either 0 or 1
+    pub const fn active_sector(&self) -> u8 {
+        self.sector
+    }
+
+    /// How many bytes of the active sector are in use, including the header
+    pub const fn used_bytes(&self) -> u32 {
+        self.end
+    }
+
+    /// Records start after the header, padded out to the write size
+    fn data_start() -> u32 {
+        aligned::<S>(HEADER_SIZE) as u32
+    }
+
+    /// Index every record in the active sector
+    fn scan(&mut self) -> Result<(), StoreError<S::Error>> {
+        let mut offset = Self::data_start();
+        while offset + RECORD_HEADER_SIZE as u32 <= S::SECTOR_SIZE {
+            let mut header = [0; RECORD_HEADER_SIZE];
+            self.read(self.sector, offset, &mut header)?;
+            if header.iter().all(|&byte| byte == ERASED) {
+                break;
+            }
+
+            let size = record_size::<S>(header[2]) as u32;
+            if usize::from(header[0]) >= MAX_KEYS || offset + size > S::SECTOR_SIZE {
+                // There is no telling where the next record starts, so treat the sector as
+                // full and start afresh in the other one on the next save
+                offset = S::SECTOR_SIZE;
+                break;
+            }
+
+            let mut buffer = [0; RECORD_BUFFER_SIZE];
+            // Records torn by losing power are skipped over
+            if let Some(stored) = self.read_record(offset, &mut buffer)? {
+                self.index[usize::from(stored.key)] =
+                    (stored.version != TOMBSTONE).then_some(offset);
+            }
+            offset += size;
+        }
+        self.end = offset;
+        Ok(())
+    }
+
+    /// Read the record at `offset` of the active sector into `buffer`, if it is intact
+    fn read_record<'a>(
+        &mut self,
+        offset: u32,
+        buffer: &'a mut [u8; RECORD_BUFFER_SIZE],
+    ) -> Result<Option<StoredRecord<'a>>, StoreError<S::Error>> {
+        self.read(self.sector, offset, &mut buffer[..RECORD_HEADER_SIZE])?;
+        let len = usize::from(buffer[2]);
+        let size = RECORD_HEADER_SIZE + len + CRC_SIZE;
+        self.read(
+            self.sector,
+            offset + RECORD_HEADER_SIZE as u32,
+            &mut buffer[RECORD_HEADER_SIZE..size],
+        )?;
+
+        let (contents, crc) = buffer[..size].split_at(RECORD_HEADER_SIZE + len);
+        if crc32(contents).to_le_bytes() != crc {
+            return Ok(None);
+        }
+        Ok(Some(StoredRecord {
+            key: contents[0],
+            version: contents[1],
+            payload: &contents[RECORD_HEADER_SIZE..],
+        }))
+    }
+
+    fn write_record(
+        &mut self,
+        key: u8,
+        version: u8,
+        payload: &[u8],
+    ) -> Result<(), StoreError<S::Error>> {
+        let index = key_index(key)?;
+        let len = payload.len().min(MAX_RECORD_SIZE);
+        let payload = &payload[..len];
+
+        // Rewriting a record that has not changed would only wear out the flash sooner
+        if let Some(offset) = self.index[index] {
+            let mut buffer = [0; RECORD_BUFFER_SIZE];
+            if let Some(current) = self.read_record(offset, &mut buffer)? {
+                if current.version == version && current.payload == payload {
+                    return Ok(());
+                }
+            }
+        }
+
+        let size = record_size::<S>(len as u8);
+        if self.end as usize + size > S::SECTOR_SIZE as usize {
+            self.compact()?;
+            if self.end as usize + size > S::SECTOR_SIZE as usize {
+                return Err(StoreError::Full);
+            }
+        }
+
+        let mut buffer = [ERASED; RECORD_BUFFER_SIZE];
+        buffer[..RECORD_HEADER_SIZE].copy_from_slice(&[key, version, len as u8, 0x00]);
+        buffer[RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + len].copy_from_slice(payload);
+        let crc = crc32(&buffer[..RECORD_HEADER_SIZE + len]);
+        buffer[RECORD_HEADER_SIZE + len..RECORD_HEADER_SIZE + len + CRC_SIZE]
+            .copy_from_slice(&crc.to_le_bytes());
+
+        let offset = self.end;
+        // Whatever happens, the bytes may have been partly written and cannot be reused
+        self.end += size as u32;
+        self.write(self.sector, offset, &buffer[..size])?;
+        self.index[index] = (version != TOMBSTONE).then_some(offset);
+        Ok(())
+    }
+
+    /// Move the latest copy of every record over to the other sector, and switch to it
+    fn compact(&mut self) -> Result<(), StoreError<S::Error>> {
+        let target = 1 - self.sector;
+        self.storage.erase(target).map_err(StoreError::Storage)?;
+
+        let mut end = Self::data_start();
+        let mut index = [None; MAX_KEYS];
+        for (key, offset) in self.index.into_iter().enumerate() {
+            let Some(offset) = offset else {
+                continue;
+            };
+            let mut buffer = [ERASED; RECORD_BUFFER_SIZE];
+            self.read(self.sector, offset, &mut buffer[..RECORD_HEADER_SIZE])?;
+            let size = record_size::<S>(buffer[2]);
+            self.read(self.sector, offset, &mut buffer[..size])?;
+            self.write(target, end, &buffer[..size])?;
+            index[key] = Some(end);
+            end += size as u32;
+        }
+
+        let generation = self.generation.wrapping_add(1);
+        self.write_header(target, generation)?;
+        self.sector = target;
+        self.generation = generation;
+        self.end = end;
+        self.index = index;
+        Ok(())
+    }
+
+    fn write_header(&mut self, sector: u8, generation: u32) -> Result<(), StoreError<S::Error>> {
+        let mut header = [ERASED; MAX_WRITE_SIZE];
+        header[..2].copy_from_slice(&MAGIC.to_le_bytes());
+        header[2] = FORMAT_VERSION;
+        header[3] = 0x00;
+        header[4..HEADER_SIZE].copy_from_slice(&generation.to_le_bytes());
+        self.write(sector, 0, &header[..Self::data_start() as usize])
+    }
+
+    fn read(
+        &mut self,
+        sector: u8,
+        offset: u32,
+        bytes: &mut [u8],
+    ) -> Result<(), StoreError<S::Error>> {
+        self.storage
+            .read(u32::from(sector) * S::SECTOR_SIZE + offset, bytes)
+            .map_err(StoreError::Storage)
+    }
+
+    fn write(&mut self, sector: u8, offset: u32, bytes: &[u8]) -> Result<(), StoreError<S::Error>> {
+        self.storage
+            .write(u32::from(sector) * S::SECTOR_SIZE + offset, bytes)
+            .map_err(StoreError::Storage)
+    }
+}
+
+/// The generation of the provided sector, if it holds a header in the current format
+fn read_header<S: Storage>(
+    storage: &mut S,
+    sector: u8,
+) -> Result<Option<u32>, StoreError<S::Error>> {
+    let mut header = [0; HEADER_SIZE];
+    storage
+        .read(u32::from(sector) * S::SECTOR_SIZE, &mut header)
+        .map_err(StoreError::Storage)?;
+    if header[..2] != MAGIC.to_le_bytes() || header[2] != FORMAT_VERSION {
+        return Ok(None);
+    }
+    Ok(Some(u32::from_le_bytes([
+        header[4], header[5], header[6], header[7],
+    ])))
+}
+
+fn key_index<E>(key: u8) -> Result<usize, StoreError<E>> {
+    let index = usize::from(key);
+    if index < MAX_KEYS {
+        Ok(index)
+    } else {
+        Err(StoreError::InvalidKey)
+    }
+}
+
+/// Round `len` up to a whole number of writes
+fn aligned<S: Storage>(len: usize) -> usize {
+    len.next_multiple_of(S::WRITE_SIZE as usize)
+}
+
+/// The space taken up by a record with a payload of `len` bytes
+fn record_size<S: Storage>(len: u8) -> usize {
+    aligned::<S>(RECORD_HEADER_SIZE + usize::from(len) + CRC_SIZE)
+}
+
+/// The CRC-32 used by Ethernet and zip
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// [`Storage`] kept in RAM, behaving like NOR flash, for testing a [`ConfigStore`] on the host
+///
+/// Writing can only clear bits, so writing twice without erasing in between is caught. Losing
+/// power part way through a write can be simulated with [`cut_power_after`](Self::cut_power_after).
+#[cfg(feature = "sim")]
+#[derive(Debug, Clone)]
+pub struct MemoryStorage<const SECTOR_SIZE: usize> {
+    sectors: [[u8; SECTOR_SIZE]; 2],
+    erase_counts: [u32; 2],
+    power_left: Option<usize>,
+}
+
+/// The errors reported by a [`MemoryStorage`]
+#[cfg(feature = "sim")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryError {
+    /// The access was outside of the two sectors
+    OutOfBounds,
+    /// The write did not start at, or was not a multiple of, [`Storage::WRITE_SIZE`]
+    Misaligned,
+    /// The write was to bytes that had already been written since they were last erased
+    NotErased,
+    /// Power was lost, after the number of bytes set by
+    /// [`cut_power_after`](MemoryStorage::cut_power_after)
+    PowerLost,
+}
+
+#[cfg(feature = "sim")]
+impl<const SECTOR_SIZE: usize> MemoryStorage<SECTOR_SIZE> {
+    /// Create a new [`MemoryStorage`], with both sectors erased
+    pub const fn new() -> Self {
+        Self {
+            sectors: [[ERASED; SECTOR_SIZE]; 2],
+            erase_counts: [0; 2],
+            power_left: None,
+        }
+    }
+
+    /// Lose power once this many more bytes have been written, failing that write part way
+    /// through and every erase or write after it
+    pub fn cut_power_after(&mut self, bytes: usize) {
+        self.power_left = Some(bytes);
+    }
+
+    /// Undo [`cut_power_after`](Self::cut_power_after)
+    pub fn restore_power(&mut self) {
+        self.power_left = None;
+    }
+
+    /// How many times the sector at the provided index has been erased
+    pub fn erase_count(&self, sector: u8) -> u32 {
+        self.erase_counts[usize::from(sector)]
+    }
+
+    /// The contents of the sector at the provided index
+    pub fn sector(&self, sector: u8) -> &[u8; SECTOR_SIZE] {
+        &self.sectors[usize::from(sector)]
+    }
+
+    /// The bytes at `offset`, which cannot span both sectors
+    fn bytes_mut(&mut self, offset: u32, len: usize) -> Result<&mut [u8], MemoryError> {
+        let offset = offset as usize;
+        let start = offset % SECTOR_SIZE;
+        self.sectors
+            .get_mut(offset / SECTOR_SIZE)
+            .and_then(|sector| sector.get_mut(start..start + len))
+            .ok_or(MemoryError::OutOfBounds)
+    }
+}
+
+#[cfg(feature = "sim")]
+impl<const SECTOR_SIZE: usize> Default for MemoryStorage<SECTOR_SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "sim")]
+impl<const SECTOR_SIZE: usize> Storage for MemoryStorage<SECTOR_SIZE> {
+    type Error = MemoryError;
+
+    const SECTOR_SIZE: u32 = SECTOR_SIZE as u32;
+    const WRITE_SIZE: u32 = 4;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), MemoryError> {
+        bytes.copy_from_slice(self.bytes_mut(offset, bytes.len())?);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), MemoryError> {
+        if offset % Self::WRITE_SIZE != 0 || bytes.len() % Self::WRITE_SIZE as usize != 0 {
+            return Err(MemoryError::Misaligned);
+        }
+        let power_left = self.power_left;
+        let flash = self.bytes_mut(offset, bytes.len())?;
+        if flash.iter().any(|&byte| byte != ERASED) {
+            return Err(MemoryError::NotErased);
+        }
+
+        let len = power_left.map_or(bytes.len(), |left| left.min(bytes.len()));
+        for (flash, byte) in flash.iter_mut().zip(&bytes[..len]) {
+            *flash &= byte;
+        }
+        if let Some(left) = &mut self.power_left {
+            *left -= len;
+            if len < bytes.len() {
+                return Err(MemoryError::PowerLost);
+            }
+        }
+        Ok(())
+    }
+
+    fn erase(&mut self, sector: u8) -> Result<(), MemoryError> {
+        if self.power_left == Some(0) {
+            return Err(MemoryError::PowerLost);
+        }
+        let sector = usize::from(sector);
+        *self
+            .sectors
+            .get_mut(sector)
+            .ok_or(MemoryError::OutOfBounds)? = [ERASED; SECTOR_SIZE];
+        self.erase_counts[sector] += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+//! Sensor readings sent back to the transmitter in AFHDS2A telemetry packets
+//!
+//! Each [`Sensor`] uses the same types and units as an IBUS sensor, so they are displayed by
+//! stock FlySky radios without any configuration.
+
+use crate::Rssi;
+
+/// The most sensors that fit in a single telemetry packet
+pub const MAX_SENSORS: usize = 7;
+
+/// The offset added to a temperature in tenths of a degree celsius, so that it is never negative
+const TEMPERATURE_OFFSET: i16 = 400;
+
+/// The kind of measurement a [`Sensor`] carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SensorType {
+    /// The voltage powering the receiver, in hundredths of a volt (0x00)
+    InternalVoltage,
+    /// A temperature, in tenths of a degree celsius offset by 40 degrees (0x01)
+    Temperature,
+    /// A motor speed, in revolutions per minute (0x02)
+    Rpm,
+    /// A voltage measured by the receiver, such as a battery, in hundredths of a volt (0x03)
+    ExternalVoltage,
+    /// The strength of the signal from the transmitter, in dBm (0xFC)
+    Rssi,
+    /// The percentage of packets from the transmitter that were lost (0xFE)
+    ErrorRate,
+    /// A sensor type that is not otherwise understood
+    Other(u8),
+}
+
+impl SensorType {
+    /// Parse a sensor type from the value sent on air
+    pub const fn from_raw(raw: u8) -> Self {
+        match raw {
+            0x00 => Self::InternalVoltage,
+            0x01 => Self::Temperature,
+            0x02 => Self::Rpm,
+            0x03 => Self::ExternalVoltage,
+            0xFC => Self::Rssi,
+            0xFE => Self::ErrorRate,
+            raw => Self::Other(raw),
+        }
+    }
+
+    /// The value sent on air for this sensor type
+    pub const fn raw(&self) -> u8 {
+        match self {
+            Self::InternalVoltage => 0x00,
+            Self::Temperature => 0x01,
+            Self::Rpm => 0x02,
+            Self::ExternalVoltage => 0x03,
+            Self::Rssi => 0xFC,
+            Self::ErrorRate => 0xFE,
+            Self::Other(raw) => *raw,
+        }
+    }
+}
+
+/// A single sensor reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sensor {
+    /// The kind of measurement, which sets the units of `value`
+    pub sensor_type: SensorType,
+    /// Tells apart multiple sensors of the same type
+    pub instance: u8,
+    /// The raw reading, in the units of `sensor_type`
+    pub value: u16,
+}
+
+impl Sensor {
+    /// Create a new [`Sensor`] with a raw value
+    pub const fn new(sensor_type: SensorType, instance: u8, value: u16) -> Self {
+        Self {
+            sensor_type,
+            instance,
+            value,
+        }
+    }
+
+    /// The voltage powering the receiver, in hundredths of a volt
+    pub const fn internal_voltage(centivolts: u16) -> Self {
+        Self::new(SensorType::InternalVoltage, 0, centivolts)
+    }
+
+    /// A temperature, in tenths of a degree celsius
+    pub const fn temperature(instance: u8, decidegrees: i16) -> Self {
+        let value = decidegrees.saturating_add(TEMPERATURE_OFFSET);
+        Self::new(
+            SensorType::Temperature,
+            instance,
+            if value < 0 { 0 } else { value as u16 },
+        )
+    }
+
+    /// A motor speed, in revolutions per minute
+    pub const fn rpm(instance: u8, rpm: u16) -> Self {
+        Self::new(SensorType::Rpm, instance, rpm)
+    }
+
+    /// A voltage measured by the receiver, in hundredths of a volt
+    pub const fn external_voltage(instance: u8, centivolts: u16) -> Self {
+        Self::new(SensorType::ExternalVoltage, instance, centivolts)
+    }
+
+    /// The strength of the signal from the transmitter
+    pub fn rssi(rssi: Rssi) -> Self {
+        Self::new(SensorType::Rssi, 0, rssi.dbm() as u16)
+    }
+
+    /// The percentage of packets from the transmitter that were lost
+    pub const fn error_rate(percent: u8) -> Self {
+        Self::new(SensorType::ErrorRate, 0, percent as u16)
+    }
+}
+
+/// The sensor readings carried by a single telemetry packet
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sensors {
+    sensors: [Sensor; MAX_SENSORS],
+    len: usize,
+}
+
+impl Sensors {
+    /// Create a new, empty, [`Sensors`]
+    pub const fn new() -> Self {
+        Self {
+            sensors: [Sensor::new(SensorType::Other(0xFF), 0, 0); MAX_SENSORS],
+            len: 0,
+        }
+    }
+
+    /// Add a reading, returning it back if there is no room left in the packet
+    pub fn push(&mut self, sensor: Sensor) -> Result<(), Sensor> {
+        if self.len == MAX_SENSORS {
+            return Err(sensor);
+        }
+        self.sensors[self.len] = sensor;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Remove every reading
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// The number of readings
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there are no readings
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The readings, in the order they were added
+    pub fn as_slice(&self) -> &[Sensor] {
+        &self.sensors[..self.len]
+    }
+}
+
+impl PartialEq for Sensors {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for Sensors {}
+
+impl Default for Sensors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Something that can provide sensor readings to send back to the transmitter
+///
+/// This is implemented by the application, and read by
+/// [`Receiver::update_telemetry`](crate::Receiver::update_telemetry).
+pub trait TelemetrySource {
+    /// Add the latest readings to `sensors`
+    ///
+    /// Any readings that do not fit in the packet are dropped.
+    fn read_sensors(&mut self, sensors: &mut Sensors);
+}
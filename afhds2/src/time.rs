@@ -0,0 +1,41 @@
+/// A point in time, measured in microseconds from an arbitrary epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Instant(u64);
+
+impl Instant {
+    /// Create an [`Instant`] from a number of microseconds since the epoch
+    pub const fn from_micros(micros: u64) -> Self {
+        Self(micros)
+    }
+
+    /// The number of microseconds since the epoch
+    pub const fn as_micros(&self) -> u64 {
+        self.0
+    }
+
+    /// The instant the provided number of microseconds after this one
+    pub const fn add_micros(&self, micros: u32) -> Self {
+        Self(self.0 + micros as u64)
+    }
+
+    /// The number of microseconds elapsed since `earlier`, or zero if `earlier` is later
+    pub const fn micros_since(&self, earlier: Instant) -> u64 {
+        self.0.saturating_sub(earlier.0)
+    }
+}
+
+/// A source of the current time
+pub trait Clock {
+    /// The current time
+    fn now(&self) -> Instant;
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> Instant,
+{
+    fn now(&self) -> Instant {
+        self()
+    }
+}
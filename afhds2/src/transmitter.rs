@@ -0,0 +1,321 @@
+//! The AFHDS2A transmit protocol, independent of any radio hardware
+//!
+//! The [`Transmitter`] is the counterpart to the [`Receiver`](crate::Receiver): it binds with a
+//! receiver, then sends a sticks packet every [`HOP_PERIOD_US`] while stepping through its hop
+//! table, listening for a telemetry reply after each one.
+//! [`Afhds2::poll_transmitter`](crate::Afhds2::poll_transmitter) executes its actions on real
+//! hardware.
+
+use crate::{
+    bind::BIND_CHANNELS,
+    hopping::HOP_PERIOD_US,
+    packet::{
+        BindPacket, BindPacketType, SticksPacket, TelemetryPacket, TransmitterPacket,
+        NUM_BIND_OPTIONS, NUM_HOP_CHANNELS, UNKNOWN_RECEIVER_ID,
+    },
+    protocol::{Action, Actions, Event, Protocol},
+    rng::XorShift,
+    rx_pll_channel,
+    time::Instant,
+    Channels, RawFrame, RxStatus,
+};
+
+/// How many packets addressed to the receiver are sent once it has replied, before the bind is
+/// considered complete
+pub const BIND_CONFIRMATIONS: u8 = 8;
+
+/// The lowest channel used in a generated hop table
+pub const MIN_HOP_CHANNEL: u8 = 0x14;
+
+/// The highest channel used in a generated hop table
+pub const MAX_HOP_CHANNEL: u8 = 0x96;
+
+/// The closest together two consecutive channels of a generated hop table can be
+const MIN_HOP_SPACING: u8 = 5;
+
+/// The receiver option bytes sent while binding
+const BIND_OPTIONS: [u8; NUM_BIND_OPTIONS] = [0xFF; NUM_BIND_OPTIONS];
+
+/// Something the application needs to know about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TransmitterOutput {
+    /// The bind completed with the receiver with the provided ID
+    Bound { receiver_id: u32 },
+    /// A sticks packet was sent, [`Transmitter::set_channels`] can be called before the next one
+    Sent,
+    /// A telemetry packet was received from the receiver
+    Telemetry(TelemetryPacket),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Announcing ourselves until a receiver replies, then confirming its ID with it
+    Binding {
+        receiver_id: Option<u32>,
+        confirmations: u8,
+    },
+    /// Sending sticks packets to the receiver with the provided ID
+    Bound { receiver_id: u32 },
+}
+
+/// The transmit side of the AFHDS2A protocol
+///
+/// Once created, the transmitter always has at least one [`Action`] queued. Every action returned
+/// by [`poll_action`](Self::poll_action) should be performed in order, and the outcome of each
+/// [`Action::Receive`] and [`Action::Wait`] reported with [`handle_event`](Self::handle_event).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transmitter {
+    transmitter_id: u32,
+    hop_table: [u8; NUM_HOP_CHANNELS],
+    mode: Mode,
+    slot: usize,
+    next_slot: Instant,
+    channels: Channels,
+    actions: Actions<TransmitterOutput>,
+}
+
+impl Transmitter {
+    /// Create a new [`Transmitter`] that will bind with the first receiver that replies
+    ///
+    /// The transmitter ID and hop table are generated from `seed`, so the same seed should be used
+    /// every time to stay bound to the same receivers.
+    pub fn bind(seed: u32, now: Instant) -> Self {
+        Self::new(
+            seed,
+            Mode::Binding {
+                receiver_id: None,
+                confirmations: 0,
+            },
+            now,
+        )
+    }
+
+    /// Create a new [`Transmitter`] that will send to a previously bound receiver
+    pub fn bound(seed: u32, receiver_id: u32, now: Instant) -> Self {
+        Self::new(seed, Mode::Bound { receiver_id }, now)
+    }
+
+    fn new(seed: u32, mode: Mode, now: Instant) -> Self {
+        let (transmitter_id, hop_table) = generate_identity(seed);
+        let mut transmitter = Self {
+            transmitter_id,
+            hop_table,
+            mode,
+            slot: 0,
+            next_slot: now,
+            channels: Channels::default(),
+            actions: Actions::new(),
+        };
+        transmitter.send_slot(now);
+        transmitter
+    }
+
+    /// The ID of this transmitter
+    pub const fn transmitter_id(&self) -> u32 {
+        self.transmitter_id
+    }
+
+    /// The channels this transmitter hops between
+    pub const fn hop_table(&self) -> &[u8; NUM_HOP_CHANNELS] {
+        &self.hop_table
+    }
+
+    /// The ID of the receiver, once bound
+    pub const fn receiver_id(&self) -> Option<u32> {
+        match self.mode {
+            Mode::Bound { receiver_id } => Some(receiver_id),
+            Mode::Binding { .. } => None,
+        }
+    }
+
+    /// Returns true once bound to a receiver
+    pub const fn is_bound(&self) -> bool {
+        matches!(self.mode, Mode::Bound { .. })
+    }
+
+    /// Set the value of every control channel sent from the next packet on
+    ///
+    /// Every channel is centered until this is called. Sending 16 or 18 channels requires the
+    /// receiver to be set up for the same number.
+    pub fn set_channels(&mut self, channels: Channels) {
+        self.channels = channels;
+    }
+
+    /// The next action to perform, if any
+    pub fn poll_action(&mut self) -> Option<Action<TransmitterOutput>> {
+        self.actions.pop()
+    }
+
+    /// Handle something that happened on the radio, queueing up the actions to perform next
+    pub fn handle_event(&mut self, event: Event<'_>) {
+        match event {
+            Event::FrameReceived { frame, status } => {
+                self.handle_frame(frame, &status);
+                // Nothing more is expected until the next packet is due
+                self.actions.push(Action::Wait {
+                    until: self.next_slot,
+                });
+            }
+            Event::TimerExpired { now } => self.send_slot(now),
+        }
+    }
+
+    fn handle_frame(&mut self, frame: &RawFrame, status: &RxStatus) {
+        if !status.is_valid() {
+            return;
+        }
+
+        match self.mode {
+            Mode::Binding {
+                receiver_id: None, ..
+            } => {
+                let Ok(reply) = BindPacket::from_bytes(frame.as_bytes()) else {
+                    return;
+                };
+                if reply.transmitter_id() == self.transmitter_id
+                    && reply.receiver_id() != UNKNOWN_RECEIVER_ID
+                {
+                    self.mode = Mode::Binding {
+                        receiver_id: Some(reply.receiver_id()),
+                        confirmations: 0,
+                    };
+                }
+            }
+            Mode::Bound { receiver_id } => {
+                let Ok(packet) = TelemetryPacket::from_frame(frame) else {
+                    return;
+                };
+                if packet.transmitter_id() == self.transmitter_id
+                    && packet.receiver_id() == receiver_id
+                {
+                    self.actions
+                        .push(Action::Deliver(TransmitterOutput::Telemetry(packet)));
+                }
+            }
+            Mode::Binding { .. } => {}
+        }
+    }
+
+    /// Send the packet for the current slot, then listen for a reply until the next one is due
+    fn send_slot(&mut self, now: Instant) {
+        // Skip any slots that were missed entirely, staying on the same cadence
+        while self.next_slot.add_micros(HOP_PERIOD_US) <= now {
+            self.next_slot = self.next_slot.add_micros(HOP_PERIOD_US);
+            self.slot = self.slot.wrapping_add(1);
+        }
+
+        let (channel, packet, output) = match self.mode {
+            Mode::Binding {
+                receiver_id: None, ..
+            } => {
+                let packet = BindPacket::new(
+                    BindPacketType::Bind1,
+                    self.transmitter_id,
+                    UNKNOWN_RECEIVER_ID,
+                    0x00,
+                    self.hop_table,
+                    BIND_OPTIONS,
+                );
+                (self.bind_channel(), TransmitterPacket::Bind(packet), None)
+            }
+            Mode::Binding {
+                receiver_id: Some(receiver_id),
+                confirmations,
+            } => {
+                let packet = BindPacket::new(
+                    BindPacketType::Bind2,
+                    self.transmitter_id,
+                    receiver_id,
+                    0x01,
+                    self.hop_table,
+                    BIND_OPTIONS,
+                );
+                let channel = self.bind_channel();
+                let output = self.confirm(receiver_id, confirmations);
+                (channel, TransmitterPacket::Bind(packet), output)
+            }
+            Mode::Bound { receiver_id } => {
+                let channel = self.hop_table[self.slot % NUM_HOP_CHANNELS];
+                let packet =
+                    SticksPacket::from_channels(self.transmitter_id, receiver_id, self.channels);
+                (
+                    channel,
+                    TransmitterPacket::Sticks(packet),
+                    Some(TransmitterOutput::Sent),
+                )
+            }
+        };
+        self.slot = self.slot.wrapping_add(1);
+        self.next_slot = self.next_slot.add_micros(HOP_PERIOD_US);
+
+        self.actions.push(Action::Tune(channel));
+        self.actions.push(Action::Transmit(packet.to_frame()));
+        if let Some(output) = output {
+            self.actions.push(Action::Deliver(output));
+        }
+        self.actions.push(Action::Tune(rx_pll_channel(channel)));
+        self.actions.push(Action::Receive {
+            deadline: self.next_slot,
+        });
+    }
+
+    /// The bind channel for the current slot, alternating between each of them
+    fn bind_channel(&self) -> u8 {
+        BIND_CHANNELS[self.slot % BIND_CHANNELS.len()]
+    }
+
+    /// Count another confirmation sent to the receiver, completing the bind once there are enough
+    fn confirm(&mut self, receiver_id: u32, confirmations: u8) -> Option<TransmitterOutput> {
+        let confirmations = confirmations + 1;
+        if confirmations < BIND_CONFIRMATIONS {
+            self.mode = Mode::Binding {
+                receiver_id: Some(receiver_id),
+                confirmations,
+            };
+            return None;
+        }
+
+        self.mode = Mode::Bound { receiver_id };
+        // The first sticks packet goes out on the first channel of the hop table
+        self.slot = usize::MAX;
+        Some(TransmitterOutput::Bound { receiver_id })
+    }
+}
+
+impl Protocol for Transmitter {
+    type Output = TransmitterOutput;
+
+    fn poll_action(&mut self) -> Option<Action<TransmitterOutput>> {
+        Transmitter::poll_action(self)
+    }
+
+    fn handle_event(&mut self, event: Event<'_>) {
+        Transmitter::handle_event(self, event)
+    }
+}
+
+/// Generate a transmitter ID and hop table from the provided seed
+fn generate_identity(seed: u32) -> (u32, [u8; NUM_HOP_CHANNELS]) {
+    let mut rng = XorShift::new(seed);
+
+    let mut transmitter_id = rng.next_u32();
+    while transmitter_id == 0 || transmitter_id == UNKNOWN_RECEIVER_ID {
+        transmitter_id = rng.next_u32();
+    }
+
+    let range = (MAX_HOP_CHANNEL - MIN_HOP_CHANNEL + 1) as u32;
+    let mut hop_table = [0; NUM_HOP_CHANNELS];
+    let mut len = 0;
+    while len < NUM_HOP_CHANNELS {
+        let channel = MIN_HOP_CHANNEL + (rng.next_u32() % range) as u8;
+        let used = hop_table[..len].contains(&channel);
+        let too_close = len > 0 && hop_table[len - 1].abs_diff(channel) < MIN_HOP_SPACING;
+        if !used && !too_close {
+            hop_table[len] = channel;
+            len += 1;
+        }
+    }
+
+    (transmitter_id, hop_table)
+}
@@ -0,0 +1,142 @@
+use afhds2::{
+    packet::{SticksPacket, TransmitterPacket},
+    ChannelCount, Channels, Failsafe, CENTER_US, MAX_US, MIN_US, NORMALIZED_MAX,
+};
+
+/// Every value a 12 bit channel can hold, spread across the channels
+fn channels(count: ChannelCount, offset: u16) -> Channels {
+    let values: [u16; 18] = core::array::from_fn(|i| (offset + i as u16 * 227) & 0x0FFF);
+    Channels::from_slice(&values[..count.num_channels()]).unwrap()
+}
+
+fn round_trip(channels: Channels) -> Channels {
+    let packet = SticksPacket::from_channels(1, 2, channels);
+    let Ok(TransmitterPacket::Sticks(packet)) =
+        TransmitterPacket::from_frame(&TransmitterPacket::Sticks(packet).to_frame())
+    else {
+        panic!("expected a sticks packet");
+    };
+    packet.channels(channels.count())
+}
+
+#[test]
+fn channels_round_trip() {
+    for count in [
+        ChannelCount::Fourteen,
+        ChannelCount::Sixteen,
+        ChannelCount::Eighteen,
+    ] {
+        for offset in (0..0x1000).step_by(97) {
+            let channels = channels(count, offset);
+            assert_eq!(round_trip(channels), channels);
+        }
+    }
+}
+
+#[test]
+fn fourteen_channels_are_sent_as_is() {
+    let channels = Channels::from_slice(&[0xFFFF; 14]).unwrap();
+    let packet = SticksPacket::from_channels(1, 2, channels);
+    assert_eq!(packet.sticks(), &[0xFFFF; 14]);
+}
+
+#[test]
+fn extra_channels_use_the_top_nibbles() {
+    let mut values = [CENTER_US; 18];
+    values[16] = 0x0ABC;
+    values[17] = 0x0123;
+    let channels = Channels::from_slice(&values).unwrap();
+    let packet = SticksPacket::from_channels(1, 2, channels);
+
+    let nibbles: [u16; 14] = core::array::from_fn(|i| packet.sticks()[i] >> 12);
+    assert_eq!(
+        nibbles,
+        [0xC, 0xD, 0x5, 0xC, 0xD, 0x5, 0xC, 0xB, 0xA, 0x3, 0x2, 0x1, 0x0, 0x0]
+    );
+    assert_eq!(packet.channels(ChannelCount::Eighteen), channels);
+}
+
+#[test]
+fn normalization() {
+    let channels = Channels::from_slice(&[
+        MIN_US, 1250, CENTER_US, 1750, MAX_US, 900, 2100, 1510, 1490, 1000, 1000, 1000, 1000, 1000,
+    ])
+    .unwrap();
+
+    assert_eq!(channels.micros(5), Some(MIN_US));
+    assert_eq!(channels.micros(6), Some(MAX_US));
+    assert_eq!(channels.micros(14), None);
+
+    let normalized: Vec<_> = (0..7).map(|i| channels.normalized(i).unwrap()).collect();
+    assert_eq!(
+        normalized,
+        [
+            -NORMALIZED_MAX,
+            -500,
+            0,
+            500,
+            NORMALIZED_MAX,
+            -NORMALIZED_MAX,
+            NORMALIZED_MAX
+        ]
+    );
+}
+
+#[test]
+fn deadband() {
+    let channels = Channels::from_slice(&[
+        1510, 1490, 1550, 1750, MAX_US, MIN_US, 1500, 1500, 1500, 1500, 1500, 1500, 1500, 1500,
+    ])
+    .unwrap();
+
+    assert_eq!(channels.normalized_with_deadband(0, 50), Some(0));
+    assert_eq!(channels.normalized_with_deadband(1, 50), Some(0));
+    // 100 past center, 50 past the deadband, scaled by 1000 / 950
+    assert_eq!(channels.normalized_with_deadband(2, 50), Some(52));
+    assert_eq!(channels.normalized_with_deadband(3, 0), Some(500));
+    assert_eq!(
+        channels.normalized_with_deadband(4, 50),
+        Some(NORMALIZED_MAX)
+    );
+    assert_eq!(
+        channels.normalized_with_deadband(5, 50),
+        Some(-NORMALIZED_MAX)
+    );
+}
+
+#[test]
+fn out_of_range_channels() {
+    let mut channels = Channels::new(ChannelCount::Sixteen);
+    assert_eq!(channels.out_of_range().count(), 0);
+
+    assert!(channels.set(3, 999));
+    assert!(channels.set(15, 2001));
+    assert!(!channels.set(16, 1500));
+    assert!(!channels.is_in_range(3));
+    assert!(channels.is_in_range(4));
+    assert_eq!(channels.out_of_range().collect::<Vec<_>>(), [3, 15]);
+}
+
+#[test]
+fn failsafe_fits_every_channel_count() {
+    // The upper nibbles of the words in a failsafe packet from an 18 channel radio
+    let mut positions = [None; 14];
+    positions[0] = Some(0xF000 | 1100);
+    positions[13] = Some(0xA000 | 1900);
+    let failsafe = Failsafe::new(positions);
+
+    for count in [ChannelCount::Sixteen, ChannelCount::Eighteen] {
+        let last = channels(count, 0x123);
+        let mut sticks = last;
+        failsafe.apply(&mut sticks);
+        assert_eq!(sticks.raw(0), Some(1100));
+        assert_eq!(sticks.raw(13), Some(1900));
+        assert_eq!(&sticks.as_slice()[1..13], &last.as_slice()[1..13]);
+        assert_eq!(&sticks.as_slice()[14..], &last.as_slice()[14..]);
+        assert_eq!(round_trip(sticks), sticks);
+    }
+
+    let mut sticks = Channels::default();
+    failsafe.apply(&mut sticks);
+    assert_eq!(sticks.raw(0), Some(0xF000 | 1100));
+}
@@ -0,0 +1,62 @@
+#![allow(dead_code)]
+
+use afhds2::{
+    bind::BindResult,
+    hopping::HOP_PERIOD_US,
+    packet::{BindPacket, BindPacketType, SticksPacket, TransmitterPacket, NUM_HOP_CHANNELS},
+    sim::SimRadio,
+    time::Instant,
+    Channels, RawFrame,
+};
+
+pub const TRANSMITTER_ID: u32 = 0x1234_5678;
+pub const RECEIVER_ID: u32 = 0x8765_4321;
+pub const HOP_TABLE: [u8; NUM_HOP_CHANNELS] = [
+    0x0a, 0x5a, 0x14, 0x64, 0x1e, 0x6e, 0x28, 0x78, 0x32, 0x82, 0x3c, 0x8c, 0x46, 0x96, 0x50, 0xa0,
+];
+
+pub fn sticks_frame(channels: Channels) -> RawFrame {
+    TransmitterPacket::Sticks(SticksPacket::from_channels(
+        TRANSMITTER_ID,
+        RECEIVER_ID,
+        channels,
+    ))
+    .to_frame()
+}
+
+pub fn bind_frame(receiver_id: u32) -> RawFrame {
+    TransmitterPacket::Bind(BindPacket::new(
+        BindPacketType::Bind1,
+        TRANSMITTER_ID,
+        receiver_id,
+        0x00,
+        HOP_TABLE,
+        [0; 10],
+    ))
+    .to_frame()
+}
+
+/// The sticks sent in the packet at the provided position of [`send_hopping`]
+pub fn hopping_sticks(index: u32) -> Channels {
+    Channels::from_slice(&[1000 + index as u16; 14]).unwrap()
+}
+
+/// Send `packets` sticks packets following [`HOP_TABLE`], the first arriving at `start`
+pub fn send_hopping(sim: &SimRadio, start: Instant, packets: u32) {
+    for i in 0..packets {
+        let channel = HOP_TABLE[i as usize % NUM_HOP_CHANNELS];
+        sim.send(
+            start.add_micros(i * HOP_PERIOD_US),
+            channel,
+            sticks_frame(hopping_sticks(i)),
+        );
+    }
+}
+
+pub fn bind_result() -> BindResult {
+    BindResult {
+        transmitter_id: TRANSMITTER_ID,
+        hop_table: HOP_TABLE,
+        options: [0; 10],
+    }
+}
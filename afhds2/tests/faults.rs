@@ -0,0 +1,322 @@
+mod common;
+
+use afhds2::{
+    hopping::{HOP_PERIOD_US, MAX_MISSED_HOPS, RESYNC_DWELL_US},
+    packet::{TelemetryPacket, TransmitterPacket},
+    sim::{Faults, SimRadio, SimSpiError, DEFAULT_RSSI},
+    Afhds2, CalibrationError, CalibrationStage, Channels, Error, Output, Receiver, Rssi,
+    FAILSAFE_TIMEOUT_US,
+};
+use common::*;
+
+#[test]
+fn configure_radio_reports_spi_errors() {
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    sim.set_faults(Faults {
+        spi_error_after: Some(5),
+        ..Default::default()
+    });
+
+    assert_eq!(
+        radio.configure_radio(sim.delay()),
+        Err(Error::Spi(SimSpiError))
+    );
+}
+
+#[test]
+fn calibrate_times_out_with_stuck_bits() {
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    sim.set_faults(Faults {
+        stuck_calibration: true,
+        ..Default::default()
+    });
+
+    assert_eq!(
+        radio.calibrate(sim.delay()),
+        Err(Error::Calibration(CalibrationError::Timeout(
+            CalibrationStage::IfFilterBank
+        )))
+    );
+}
+
+#[test]
+fn receive_reports_crc_and_fec_errors() {
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    radio.set_channel(0x20).unwrap();
+
+    sim.set_faults(Faults {
+        crc_error: true,
+        ..Default::default()
+    });
+    sim.send(
+        sim.now().add_micros(1_000),
+        0x21,
+        sticks_frame(Channels::default()),
+    );
+    let (_, status) = radio.receive(sim.delay(), &sim, 5_000).unwrap();
+    assert!(status.crc_error && !status.fec_error);
+    assert_eq!(status.check::<(), ()>(), Err(Error::CrcError));
+
+    sim.set_faults(Faults {
+        fec_error: true,
+        ..Default::default()
+    });
+    sim.send(
+        sim.now().add_micros(1_000),
+        0x21,
+        sticks_frame(Channels::default()),
+    );
+    let (_, status) = radio.receive(sim.delay(), &sim, 5_000).unwrap();
+    assert!(!status.crc_error && status.fec_error);
+    assert_eq!(status.check::<(), ()>(), Err(Error::FecError));
+}
+
+#[test]
+fn receive_packet_reports_protocol_faults() {
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    radio.set_channel(0x20).unwrap();
+
+    for (faults, error) in [
+        (
+            Faults {
+                crc_error: true,
+                ..Default::default()
+            },
+            Error::CrcError,
+        ),
+        (
+            Faults {
+                fec_error: true,
+                ..Default::default()
+            },
+            Error::FecError,
+        ),
+    ] {
+        sim.set_faults(faults);
+        sim.send(
+            sim.now().add_micros(1_000),
+            0x21,
+            sticks_frame(Channels::default()),
+        );
+        assert_eq!(
+            radio.receive_packet(sim.delay(), &sim, 5_000).unwrap_err(),
+            error
+        );
+    }
+    sim.set_faults(Faults::default());
+
+    // Another receiver's telemetry is not something a transmitter sends
+    let telemetry = TelemetryPacket::new(TRANSMITTER_ID, RECEIVER_ID, Default::default());
+    sim.send(sim.now().add_micros(1_000), 0x21, telemetry.to_frame());
+    assert_eq!(
+        radio.receive_packet(sim.delay(), &sim, 5_000).unwrap_err(),
+        Error::UnexpectedPacket
+    );
+
+    sim.send(
+        sim.now().add_micros(1_000),
+        0x21,
+        sticks_frame(Channels::default()),
+    );
+    let (packet, status) = radio.receive_packet(sim.delay(), &sim, 5_000).unwrap();
+    assert!(matches!(packet, TransmitterPacket::Sticks(_)));
+    assert!(status.is_valid());
+}
+
+#[test]
+fn receive_returns_corrupted_bytes() {
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    radio.set_channel(0x20).unwrap();
+    sim.set_faults(Faults {
+        bit_flips: 3,
+        ..Default::default()
+    });
+
+    let frame = sticks_frame(Channels::default());
+    sim.send(sim.now().add_micros(1_000), 0x21, frame);
+    let (received, status) = radio.receive(sim.delay(), &sim, 5_000).unwrap();
+
+    let flipped: u32 = received
+        .as_bytes()
+        .iter()
+        .zip(frame.as_bytes())
+        .map(|(a, b)| (a ^ b).count_ones())
+        .sum();
+    assert!(flipped > 0 && flipped <= 3);
+    assert!(status.is_valid());
+}
+
+#[test]
+fn hopping_survives_packet_loss() {
+    const PACKETS: u32 = 200;
+
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    sim.set_faults(Faults {
+        packet_loss_percent: 25,
+        ..Default::default()
+    });
+    let mut receiver = Receiver::bound(RECEIVER_ID, &bind_result(), sim.now());
+    send_hopping(&sim, sim.now().add_micros(10_000), PACKETS);
+
+    let mut received = 0;
+    let mut last = None;
+    loop {
+        match radio
+            .poll_receiver(&mut receiver, sim.delay(), &sim)
+            .unwrap()
+        {
+            Output::Channels(sticks) => {
+                assert!(last.map_or(true, |last: Channels| sticks.raw(0) > last.raw(0)));
+                last = Some(sticks);
+                received += 1;
+            }
+            Output::Failsafe(_) => break,
+            Output::Bound(_) | Output::Selected(_) => panic!("already bound"),
+        }
+    }
+
+    // Around three quarters of the packets should make it through, the tracker staying in sync
+    // through the gaps rather than losing the transmitter
+    assert!(received > PACKETS / 2, "only received {received}");
+    assert!(received < PACKETS);
+    assert_eq!(last, Some(hopping_sticks(PACKETS - 1)));
+}
+
+#[test]
+fn failsafe_engages_while_packets_are_corrupted() {
+    const GOOD_PACKETS: u32 = 16;
+
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    let mut receiver = Receiver::bound(RECEIVER_ID, &bind_result(), sim.now());
+    let start = sim.now().add_micros(10_000);
+    send_hopping(&sim, start, 400);
+
+    for i in 0..GOOD_PACKETS {
+        assert_eq!(
+            radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+            Ok(Output::Channels(hopping_sticks(i)))
+        );
+    }
+    let last_good = sim.now();
+
+    sim.set_faults(Faults {
+        crc_error: true,
+        ..Default::default()
+    });
+    assert_eq!(
+        radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+        Ok(Output::Failsafe(hopping_sticks(GOOD_PACKETS - 1)))
+    );
+
+    // Corrupted packets cannot be trusted to keep the hop timing, so sync is lost too
+    assert!(!receiver.tracker().unwrap().is_synced());
+    let failsafe_after = sim.now().micros_since(last_good);
+    assert!(failsafe_after >= FAILSAFE_TIMEOUT_US as u64);
+    assert!(failsafe_after < (FAILSAFE_TIMEOUT_US + RESYNC_DWELL_US) as u64);
+}
+
+#[test]
+fn receiver_recovers_from_spi_errors() {
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    let mut receiver = Receiver::bound(RECEIVER_ID, &bind_result(), sim.now());
+    send_hopping(&sim, sim.now().add_micros(10_000), 64);
+
+    sim.set_faults(Faults {
+        spi_error_after: Some(1),
+        ..Default::default()
+    });
+    assert_eq!(
+        radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+        Err(Error::Spi(SimSpiError))
+    );
+
+    sim.set_faults(Faults::default());
+    assert!(matches!(
+        radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+        Ok(Output::Channels(_))
+    ));
+}
+
+#[test]
+fn link_stats_measure_packet_loss() {
+    const PACKETS: u32 = 600;
+
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    sim.set_faults(Faults {
+        packet_loss_percent: 25,
+        ..Default::default()
+    });
+    let mut receiver = Receiver::bound(RECEIVER_ID, &bind_result(), sim.now());
+    send_hopping(&sim, sim.now().add_micros(10_000), PACKETS);
+
+    // Stop while the transmitter is still sending, after a couple of measurement windows
+    let end = sim.now().add_micros(2_100_000);
+    while sim.now() < end {
+        radio
+            .poll_receiver(&mut receiver, sim.delay(), &sim)
+            .unwrap();
+    }
+
+    let stats = receiver.link_stats().snapshot(sim.now());
+    // A second of packets is 259 hops, of which around a quarter are lost
+    assert_eq!(stats.expected_per_second, 1_000_000 / HOP_PERIOD_US);
+    assert!((15..=35).contains(&stats.error_rate_percent()), "{stats:?}");
+    assert_eq!(stats.crc_errors + stats.fec_errors, 0);
+    assert!(stats.longest_miss_streak >= 2);
+    assert!(stats.channel_losses.iter().all(|&losses| losses > 0));
+    assert_eq!(stats.average_rssi, Some(Rssi::from_raw(DEFAULT_RSSI)));
+    assert!(
+        stats.last_packet_age_us.unwrap() < (stats.miss_streak + 1) as u64 * HOP_PERIOD_US as u64
+    );
+}
+
+#[test]
+fn link_stats_count_corrupted_packets() {
+    const PACKETS: u32 = 16;
+
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    let mut receiver = Receiver::bound(RECEIVER_ID, &bind_result(), sim.now());
+    let start = sim.now().add_micros(10_000);
+    send_hopping(&sim, start, PACKETS * 2);
+
+    for i in 0..PACKETS {
+        assert_eq!(
+            radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+            Ok(Output::Channels(hopping_sticks(i)))
+        );
+    }
+    sim.set_faults(Faults {
+        crc_error: true,
+        ..Default::default()
+    });
+    assert!(matches!(
+        radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+        Ok(Output::Failsafe(_))
+    ));
+
+    // Every hop with only a corrupted packet is a miss, until sync was lost
+    let stats = receiver.link_stats().snapshot(sim.now());
+    assert_eq!(stats.crc_errors, PACKETS);
+    assert_eq!(stats.fec_errors, 0);
+    assert_eq!(stats.miss_streak, MAX_MISSED_HOPS as u32);
+    assert_eq!(stats.channel_losses, [1; 16]);
+}
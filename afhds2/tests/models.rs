@@ -0,0 +1,306 @@
+mod common;
+
+use afhds2::{
+    bind::BindResult,
+    hopping::{HOP_PERIOD_US, RESYNC_DWELL_US},
+    packet::{FailsafePacket, SticksPacket, TransmitterPacket, NUM_HOP_CHANNELS},
+    sim::SimRadio,
+    telemetry::{Sensor, Sensors, TelemetrySource},
+    time::Instant,
+    Afhds2, ChannelCount, Channels, Failsafe, Model, ModelMemory, Output, Receiver, MAX_MODELS,
+};
+use common::*;
+
+const OTHER_TRANSMITTER_ID: u32 = 0x0BAD_F00D;
+const OTHER_HOP_TABLE: [u8; NUM_HOP_CHANNELS] = [
+    0x19, 0x69, 0x23, 0x73, 0x2d, 0x7d, 0x37, 0x87, 0x41, 0x91, 0x4b, 0x9b, 0x55, 0x5f, 0x2a, 0x4f,
+];
+
+fn other_bind_result() -> BindResult {
+    BindResult {
+        transmitter_id: OTHER_TRANSMITTER_ID,
+        hop_table: OTHER_HOP_TABLE,
+        options: [0; 10],
+    }
+}
+
+fn bind_result_for(transmitter_id: u32) -> BindResult {
+    BindResult {
+        transmitter_id,
+        ..bind_result()
+    }
+}
+
+#[test]
+fn model_memory_add_select_forget() {
+    let mut models = ModelMemory::new();
+    assert!(models.is_empty());
+    assert_eq!(models.active(), None);
+
+    models.add(bind_result()).unwrap();
+    models.add(other_bind_result()).unwrap();
+    assert_eq!(models.len(), 2);
+    // The most recently bound transmitter is selected
+    assert_eq!(
+        models.active().map(Model::transmitter_id),
+        Some(OTHER_TRANSMITTER_ID)
+    );
+
+    assert!(models.select(TRANSMITTER_ID));
+    assert!(!models.select(0x1111_1111));
+    assert_eq!(models.active(), Some(&Model::new(bind_result())));
+
+    // Binding the same transmitter again replaces it rather than taking up another slot
+    models.get_mut(TRANSMITTER_ID).unwrap().channel_count = ChannelCount::Sixteen;
+    models.add(bind_result()).unwrap();
+    assert_eq!(models.len(), 2);
+    assert_eq!(
+        models.get(TRANSMITTER_ID).unwrap().channel_count,
+        ChannelCount::Fourteen
+    );
+
+    assert_eq!(
+        models.forget(TRANSMITTER_ID),
+        Some(Model::new(bind_result()))
+    );
+    assert_eq!(models.forget(TRANSMITTER_ID), None);
+    assert_eq!(models.active(), None);
+    assert_eq!(
+        models.iter().map(Model::transmitter_id).collect::<Vec<_>>(),
+        [OTHER_TRANSMITTER_ID]
+    );
+}
+
+#[test]
+fn model_memory_is_limited() {
+    let mut models = ModelMemory::new();
+    for i in 0..MAX_MODELS as u32 {
+        models.add(bind_result_for(0x1000 + i)).unwrap();
+    }
+    assert!(models.is_full());
+    assert_eq!(
+        models.add(bind_result_for(0x2000)),
+        Err(bind_result_for(0x2000))
+    );
+
+    // Forgetting one makes room for another
+    models.forget(0x1001);
+    models.add(bind_result_for(0x2000)).unwrap();
+    assert!(models.is_full());
+    assert!(models.get(0x2000).is_some());
+}
+
+#[test]
+fn receiver_binds_without_models() {
+    let sim = SimRadio::new();
+    let receiver = Receiver::from_models(RECEIVER_ID, &ModelMemory::new(), sim.now());
+    assert!(!receiver.is_bound());
+}
+
+#[test]
+fn receiver_selects_the_transmitter_it_hears() {
+    const PACKETS: u32 = 40;
+
+    let mut models = ModelMemory::new();
+    models.add(other_bind_result()).unwrap();
+    models.add(bind_result()).unwrap();
+    let failsafe = [Some(1100); 14];
+    models.get_mut(OTHER_TRANSMITTER_ID).unwrap().failsafe = Failsafe::new(failsafe);
+
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    let mut receiver = Receiver::from_models(RECEIVER_ID, &models, sim.now());
+
+    // Only the transmitter that is not active is on, so the receiver has to try both
+    let start = sim.now().add_micros(10_000);
+    for i in 0..PACKETS {
+        let packet = SticksPacket::from_channels(
+            OTHER_TRANSMITTER_ID,
+            RECEIVER_ID,
+            Channels::from_slice(&[1000 + i as u16; 14]).unwrap(),
+        );
+        sim.send(
+            start.add_micros(i * HOP_PERIOD_US),
+            OTHER_HOP_TABLE[i as usize % NUM_HOP_CHANNELS],
+            TransmitterPacket::Sticks(packet).to_frame(),
+        );
+    }
+
+    assert_eq!(
+        radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+        Ok(Output::Selected(OTHER_TRANSMITTER_ID))
+    );
+    let selected_at = sim.now();
+    // The active transmitter is listened for first, for one dwell
+    assert!(selected_at.micros_since(start) >= RESYNC_DWELL_US as u64 - 10_000);
+    assert!(selected_at.micros_since(start) < 2 * RESYNC_DWELL_US as u64);
+
+    let Ok(Output::Channels(first)) = radio.poll_receiver(&mut receiver, sim.delay(), &sim) else {
+        panic!("expected channels");
+    };
+    for i in 1..4 {
+        let expected = first.raw(0).unwrap() + i;
+        assert_eq!(
+            radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+            Ok(Output::Channels(
+                Channels::from_slice(&[expected; 14]).unwrap()
+            ))
+        );
+    }
+
+    // The failsafe stored for the transmitter is used until it sends its own
+    assert_eq!(receiver.failsafe(), &Failsafe::new(failsafe));
+    let model = receiver.model().unwrap();
+    assert_eq!(model.bind, other_bind_result());
+    assert_eq!(model.failsafe, Failsafe::new(failsafe));
+}
+
+#[test]
+fn receiver_model_tracks_failsafe() {
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    let mut models = ModelMemory::new();
+    models.add(bind_result()).unwrap();
+    let mut receiver = Receiver::from_models(RECEIVER_ID, &models, sim.now());
+
+    let start = sim.now().add_micros(10_000);
+    send_hopping(&sim, start, 1);
+    let failsafe = FailsafePacket::new(TRANSMITTER_ID, RECEIVER_ID, [Some(1234); 14]);
+    sim.send(
+        start.add_micros(HOP_PERIOD_US),
+        HOP_TABLE[1],
+        TransmitterPacket::Failsafe(failsafe).to_frame(),
+    );
+    send_hopping_from(&sim, start, 2);
+
+    assert_eq!(
+        radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+        Ok(Output::Selected(TRANSMITTER_ID))
+    );
+    assert_eq!(
+        radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+        Ok(Output::Channels(hopping_sticks(0)))
+    );
+    assert_eq!(
+        radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+        Ok(Output::Channels(hopping_sticks(2)))
+    );
+
+    *models.get_mut(TRANSMITTER_ID).unwrap() = receiver.model().unwrap();
+    assert_eq!(
+        models.active().unwrap().failsafe,
+        Failsafe::new([Some(1234); 14])
+    );
+}
+
+#[test]
+fn receiver_locks_on_to_any_stored_transmitter_it_hears() {
+    const PACKETS: u32 = 24;
+
+    // The same channels as the active transmitter, in another order
+    let mut hop_table = HOP_TABLE;
+    hop_table.rotate_left(5);
+    let mut models = ModelMemory::new();
+    models
+        .add(BindResult {
+            hop_table,
+            ..other_bind_result()
+        })
+        .unwrap();
+    models.add(bind_result()).unwrap();
+
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    let mut receiver = Receiver::from_models(RECEIVER_ID, &models, sim.now());
+
+    let start = sim.now().add_micros(10_000);
+    for i in 0..PACKETS {
+        let packet = SticksPacket::from_channels(
+            OTHER_TRANSMITTER_ID,
+            RECEIVER_ID,
+            Channels::from_slice(&[1000 + i as u16; 14]).unwrap(),
+        );
+        sim.send(
+            start.add_micros(i * HOP_PERIOD_US),
+            hop_table[i as usize % NUM_HOP_CHANNELS],
+            TransmitterPacket::Sticks(packet).to_frame(),
+        );
+    }
+
+    // Heard on the active transmitter's channel, without waiting for its own turn
+    assert_eq!(
+        radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+        Ok(Output::Selected(OTHER_TRANSMITTER_ID))
+    );
+    assert!(sim.now().micros_since(start) < RESYNC_DWELL_US as u64);
+    assert_eq!(receiver.model().unwrap().bind.hop_table, hop_table);
+
+    // The first channel of the active transmitter's hop table is further along the other's
+    let first = hop_table
+        .iter()
+        .position(|&channel| channel == HOP_TABLE[0])
+        .unwrap() as u16;
+    for i in first..first + 4 {
+        assert_eq!(
+            radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+            Ok(Output::Channels(
+                Channels::from_slice(&[1000 + i; 14]).unwrap()
+            ))
+        );
+    }
+}
+
+/// Send the packets of [`send_hopping`] from `from` onwards, up to the end of the hop table
+fn send_hopping_from(sim: &SimRadio, start: Instant, from: u32) {
+    for i in from..NUM_HOP_CHANNELS as u32 {
+        sim.send(
+            start.add_micros(i * HOP_PERIOD_US),
+            HOP_TABLE[i as usize],
+            sticks_frame(hopping_sticks(i)),
+        );
+    }
+}
+
+struct Battery;
+
+impl TelemetrySource for Battery {
+    fn read_sensors(&mut self, sensors: &mut Sensors) {
+        let _ = sensors.push(Sensor::external_voltage(0, 1200));
+    }
+}
+
+#[test]
+fn locking_on_with_telemetry_fills_the_action_queue() {
+    let mut models = ModelMemory::new();
+    models.add(bind_result()).unwrap();
+
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    let mut receiver = Receiver::from_models(RECEIVER_ID, &models, sim.now());
+    receiver.update_telemetry(&mut Battery);
+    send_hopping(&sim, sim.now().add_micros(10_000), 2);
+
+    // The first packet selects the transmitter, delivers its sticks, replies with telemetry and
+    // listens for the next hop, which is as many actions as can be queued at once
+    assert_eq!(
+        radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+        Ok(Output::Selected(TRANSMITTER_ID))
+    );
+    assert_eq!(
+        radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+        Ok(Output::Channels(hopping_sticks(0)))
+    );
+    assert_eq!(
+        radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+        Ok(Output::Channels(hopping_sticks(1)))
+    );
+
+    // The reply to the second packet is only sent on the next poll
+    let transmissions = sim.transmissions();
+    assert_eq!(transmissions.len(), 1);
+    assert_eq!(transmissions[0].channel, HOP_TABLE[0]);
+}
@@ -0,0 +1,178 @@
+use afhds2::packet::{
+    BindPacket, BindPacketType, FailsafePacket, SettingsPacket, SticksPacket, TelemetryPacket,
+    TransmitterPacket, NUM_BIND_OPTIONS, NUM_CONTROL_CHANNELS, NUM_HOP_CHANNELS,
+};
+use afhds2::telemetry::{Sensor, SensorType, Sensors, MAX_SENSORS};
+use afhds2::PACKET_SIZE;
+
+const CASES: usize = 1_000;
+
+/// A small xorshift generator, so that every run checks the same packets
+struct Rng(u32);
+
+impl Rng {
+    fn u32(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    fn u16(&mut self) -> u16 {
+        self.u32() as u16
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.u32() as u8
+    }
+
+    fn bool(&mut self) -> bool {
+        self.u32() & 1 != 0
+    }
+
+    fn array<const N: usize, T>(&mut self, mut f: impl FnMut(&mut Self) -> T) -> [T; N] {
+        core::array::from_fn(|_| f(self))
+    }
+}
+
+fn sticks(rng: &mut Rng) -> SticksPacket {
+    SticksPacket::new(rng.u32(), rng.u32(), rng.array(Rng::u16))
+}
+
+fn bind(rng: &mut Rng) -> BindPacket {
+    let packet_type = if rng.bool() {
+        BindPacketType::Bind1
+    } else {
+        BindPacketType::Bind2
+    };
+    BindPacket::new(
+        packet_type,
+        rng.u32(),
+        rng.u32(),
+        rng.u8(),
+        rng.array::<NUM_HOP_CHANNELS, _>(Rng::u8),
+        rng.array::<NUM_BIND_OPTIONS, _>(Rng::u8),
+    )
+}
+
+fn failsafe(rng: &mut Rng) -> FailsafePacket {
+    // 0xFFFF marks a disabled channel on air, so is not a position that can round trip
+    let positions = rng.array::<NUM_CONTROL_CHANNELS, _>(|rng| {
+        let position = rng.u16();
+        (rng.bool() && position != 0xFFFF).then_some(position)
+    });
+    FailsafePacket::new(rng.u32(), rng.u32(), positions)
+}
+
+fn settings(rng: &mut Rng) -> SettingsPacket {
+    SettingsPacket::new(rng.u32(), rng.u32(), rng.u16(), rng.bool(), rng.bool())
+}
+
+fn telemetry(rng: &mut Rng) -> TelemetryPacket {
+    const TYPES: [SensorType; 7] = [
+        SensorType::InternalVoltage,
+        SensorType::Temperature,
+        SensorType::Rpm,
+        SensorType::ExternalVoltage,
+        SensorType::Rssi,
+        SensorType::ErrorRate,
+        SensorType::Other(0x41),
+    ];
+
+    let mut sensors = Sensors::new();
+    for _ in 0..rng.u32() as usize % (MAX_SENSORS + 1) {
+        let sensor_type = TYPES[rng.u32() as usize % TYPES.len()];
+        sensors
+            .push(Sensor::new(sensor_type, rng.u8(), rng.u16()))
+            .unwrap();
+    }
+    TelemetryPacket::new(rng.u32(), rng.u32(), sensors)
+}
+
+fn round_trip(packet: TransmitterPacket) {
+    let mut bytes = [0; PACKET_SIZE];
+    packet.to_bytes(&mut bytes);
+    assert_eq!(TransmitterPacket::from_bytes(&bytes), Ok(packet));
+}
+
+#[test]
+fn sticks_round_trip() {
+    let mut rng = Rng(1);
+    for _ in 0..CASES {
+        round_trip(TransmitterPacket::Sticks(sticks(&mut rng)));
+    }
+}
+
+#[test]
+fn bind_round_trip() {
+    let mut rng = Rng(2);
+    for _ in 0..CASES {
+        round_trip(TransmitterPacket::Bind(bind(&mut rng)));
+    }
+}
+
+#[test]
+fn failsafe_round_trip() {
+    let mut rng = Rng(3);
+    for _ in 0..CASES {
+        round_trip(TransmitterPacket::Failsafe(failsafe(&mut rng)));
+    }
+}
+
+#[test]
+fn settings_round_trip() {
+    let mut rng = Rng(4);
+    for _ in 0..CASES {
+        round_trip(TransmitterPacket::Settings(settings(&mut rng)));
+    }
+}
+
+#[test]
+fn settings_layout() {
+    let mut bytes = [0; PACKET_SIZE];
+    SettingsPacket::new(0x0403_0201, 0x0807_0605, 50, true, false).to_bytes(&mut bytes);
+
+    assert_eq!(
+        bytes[..9],
+        [0xAA, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]
+    );
+    assert_eq!(bytes[9..15], [0xFD, 0xFF, 50, 0x00, 0x01, 0x00]);
+    assert!(bytes[15..].iter().all(|&b| b == 0xFF));
+}
+
+#[test]
+fn telemetry_round_trip() {
+    let mut rng = Rng(5);
+    for _ in 0..CASES {
+        let packet = telemetry(&mut rng);
+        let mut bytes = [0; PACKET_SIZE];
+        packet.to_bytes(&mut bytes);
+        assert_eq!(TelemetryPacket::from_bytes(&bytes), Ok(packet));
+    }
+}
+
+#[test]
+fn telemetry_layout() {
+    let mut sensors = Sensors::new();
+    sensors.push(Sensor::internal_voltage(0x01F4)).unwrap();
+    sensors.push(Sensor::temperature(1, 250)).unwrap();
+    let mut bytes = [0; PACKET_SIZE];
+    TelemetryPacket::new(0x0403_0201, 0x0807_0605, sensors).to_bytes(&mut bytes);
+
+    assert_eq!(
+        bytes[..9],
+        [0xAA, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]
+    );
+    assert_eq!(bytes[9..13], [0x00, 0x00, 0xF4, 0x01]);
+    // Temperatures are offset by 40 degrees
+    assert_eq!(bytes[13..17], [0x01, 0x01, 0x8A, 0x02]);
+    assert!(bytes[17..].iter().all(|&b| b == 0xFF));
+}
+
+#[test]
+fn settings_are_not_telemetry() {
+    let mut bytes = [0; PACKET_SIZE];
+    SettingsPacket::new(1, 2, 50, false, true).to_bytes(&mut bytes);
+
+    assert!(TelemetryPacket::from_bytes(&bytes).is_err());
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use afhds2::{
+    bind::BIND_CHANNELS,
+    packet::{BindPacket, UNKNOWN_RECEIVER_ID},
+    sim::SimRadio,
+    Afhds2, Output, Receiver, ReceiverId,
+};
+use common::*;
+
+/// The 96 bit unique ID of an STM32, with the lot and wafer the same for every chip in a batch
+fn uid(serial: u32) -> [u8; 12] {
+    let mut uid = *b"\x00\x00\x00\x00LOT0042\x07";
+    uid[..4].copy_from_slice(&serial.to_le_bytes());
+    uid
+}
+
+#[test]
+fn receiver_id_is_stable() {
+    assert_eq!(
+        ReceiverId::from_seed(&uid(7)),
+        ReceiverId::from_seed(&uid(7))
+    );
+    assert_eq!(
+        ReceiverId::from_raw(ReceiverId::from_seed(&uid(7)).raw()),
+        Some(ReceiverId::from_seed(&uid(7)))
+    );
+}
+
+#[test]
+fn receiver_ids_are_unique() {
+    let mut ids: Vec<_> = (0..1_000)
+        .map(|serial| ReceiverId::from_seed(&uid(serial)).raw())
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    assert_eq!(ids.len(), 1_000);
+    assert!(!ids.contains(&0));
+    assert!(!ids.contains(&UNKNOWN_RECEIVER_ID));
+
+    assert_ne!(ReceiverId::from_seed(&[]), ReceiverId::from_seed(&[0]),);
+}
+
+#[test]
+fn invalid_receiver_ids_are_rejected() {
+    assert_eq!(ReceiverId::from_raw(0), None);
+    assert_eq!(ReceiverId::from_raw(UNKNOWN_RECEIVER_ID), None);
+    assert_eq!(
+        ReceiverId::from_raw(RECEIVER_ID).map(u32::from),
+        Some(RECEIVER_ID)
+    );
+}
+
+#[test]
+fn bind_reply_carries_receiver_id() {
+    let receiver_id = ReceiverId::from_seed(&uid(7));
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    let mut receiver = Receiver::bind(receiver_id.raw(), sim.now());
+
+    let start = sim.now();
+    sim.send(
+        start.add_micros(5_000),
+        BIND_CHANNELS[0],
+        bind_frame(UNKNOWN_RECEIVER_ID),
+    );
+    sim.send(
+        start.add_micros(15_000),
+        BIND_CHANNELS[0],
+        bind_frame(receiver_id.raw()),
+    );
+    assert_eq!(
+        radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+        Ok(Output::Bound(bind_result()))
+    );
+
+    let reply = BindPacket::from_bytes(sim.transmissions()[0].frame.as_bytes()).unwrap();
+    assert_eq!(reply.receiver_id(), receiver_id.raw());
+}
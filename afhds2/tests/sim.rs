@@ -0,0 +1,280 @@
+mod common;
+
+use afhds2::{
+    bind::BIND_CHANNELS,
+    hopping::{HOP_PERIOD_US, RESYNC_DWELL_US},
+    packet::{
+        BindPacket, SticksPacket, TelemetryPacket, TransmitterPacket, NUM_HOP_CHANNELS,
+        UNKNOWN_RECEIVER_ID,
+    },
+    sim::{SimRadio, DEFAULT_RSSI, IF_FILTER_BANK, VCO_BANK},
+    telemetry::{Sensor, Sensors, TelemetrySource},
+    Afhds2, Channels, Output, Receiver, Rssi, FAILSAFE_TIMEOUT_US, MAX_SCAN_CHANNEL,
+    NUM_SCAN_CHANNELS, RADIO_ID,
+};
+use common::*;
+
+#[test]
+fn configure_radio_writes_config() {
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+
+    radio.configure_radio(sim.delay()).unwrap();
+
+    assert!(radio.verify_configuration().unwrap().is_empty());
+    assert_eq!(sim.id(), RADIO_ID);
+    assert_eq!(sim.channel(), 0x50);
+}
+
+#[test]
+fn calibrate_reports_results() {
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+
+    let report = radio.calibrate(sim.delay()).unwrap();
+
+    assert_eq!(report.if_filter_bank, IF_FILTER_BANK);
+    assert_eq!(report.vco_current, 0x03);
+    assert_eq!(report.vco_bank, [VCO_BANK; 2]);
+}
+
+#[test]
+fn receive_frame_on_tuned_channel() {
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    radio.set_channel(0x20).unwrap();
+
+    let frame = sticks_frame(Channels::default());
+    sim.send(sim.now().add_micros(1_000), 0x21, frame);
+    let (received, status) = radio.receive(sim.delay(), &sim, 5_000).unwrap();
+
+    assert_eq!(received, frame);
+    assert!(status.is_valid());
+    assert_eq!(status.channel, 0x20);
+}
+
+#[test]
+fn receive_times_out_on_other_channel() {
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    radio.set_channel(0x20).unwrap();
+
+    sim.send(
+        sim.now().add_micros(1_000),
+        0x30,
+        sticks_frame(Channels::default()),
+    );
+
+    assert_eq!(
+        radio.receive(sim.delay(), &sim, 5_000),
+        Err(afhds2::Error::Timeout)
+    );
+    assert!(!sim.is_receiving());
+}
+
+#[test]
+fn spectrum_scan_finds_interference() {
+    const DWELL_US: u32 = 800;
+
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    sim.set_rssi(200);
+    sim.set_interference(0x40, 40);
+
+    let start = sim.now();
+    let report = radio.spectrum_scan(sim.delay(), DWELL_US).unwrap();
+
+    assert_eq!(report.channels().count(), NUM_SCAN_CHANNELS);
+    assert_eq!(report.busy_channels(), 1);
+    assert!(report.is_busy(0x40));
+    assert_eq!(report.busiest(), 0x40);
+    assert_eq!(report.channel(0x40).unwrap().peak, Rssi::from_raw(40));
+    assert_eq!(report.channel(0x41).unwrap().average, Rssi::from_raw(200));
+    assert!(report.channel(MAX_SCAN_CHANNEL + 1).is_none());
+
+    assert!(sim.now().micros_since(start) >= NUM_SCAN_CHANNELS as u64 * DWELL_US as u64);
+    assert!(!sim.is_receiving());
+    assert_eq!(sim.channel(), MAX_SCAN_CHANNEL);
+}
+
+#[test]
+fn bind_completes_with_transmitter() {
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    let mut receiver = Receiver::bind(RECEIVER_ID, sim.now());
+
+    let start = sim.now();
+    sim.send(
+        start.add_micros(5_000),
+        BIND_CHANNELS[0],
+        bind_frame(UNKNOWN_RECEIVER_ID),
+    );
+    sim.send(
+        start.add_micros(15_000),
+        BIND_CHANNELS[0],
+        bind_frame(RECEIVER_ID),
+    );
+
+    let Output::Bound(result) = radio
+        .poll_receiver(&mut receiver, sim.delay(), &sim)
+        .unwrap()
+    else {
+        panic!("expected the bind to complete");
+    };
+    assert_eq!(result.transmitter_id, TRANSMITTER_ID);
+    assert_eq!(result.hop_table, HOP_TABLE);
+    assert!(receiver.is_bound());
+
+    let transmissions = sim.transmissions();
+    assert_eq!(transmissions.len(), 1);
+    assert_eq!(transmissions[0].channel, BIND_CHANNELS[0]);
+    let reply = BindPacket::from_bytes(transmissions[0].frame.as_bytes()).unwrap();
+    assert_eq!(reply.transmitter_id(), TRANSMITTER_ID);
+    assert_eq!(reply.receiver_id(), RECEIVER_ID);
+}
+
+#[test]
+fn hopping_receives_every_packet() {
+    const PACKETS: u32 = 48;
+
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    let mut receiver = Receiver::bound(RECEIVER_ID, &bind_result(), sim.now());
+
+    let start = sim.now().add_micros(10_000);
+    send_hopping(&sim, start, PACKETS);
+
+    let mut received = 0;
+    let failsafe_at = loop {
+        match radio
+            .poll_receiver(&mut receiver, sim.delay(), &sim)
+            .unwrap()
+        {
+            Output::Channels(sticks) => {
+                assert_eq!(sticks, hopping_sticks(received));
+                received += 1;
+            }
+            Output::Failsafe(sticks) => {
+                assert_eq!(sticks, hopping_sticks(PACKETS - 1));
+                break sim.now();
+            }
+            Output::Bound(_) | Output::Selected(_) => panic!("already bound"),
+        }
+    };
+
+    assert_eq!(received, PACKETS);
+    assert!(receiver.is_failsafe_active());
+    let last = start.add_micros((PACKETS - 1) * HOP_PERIOD_US);
+    let failsafe_after = failsafe_at.micros_since(last);
+    assert!(failsafe_after >= FAILSAFE_TIMEOUT_US as u64);
+    assert!(failsafe_after < FAILSAFE_TIMEOUT_US as u64 + RESYNC_DWELL_US as u64);
+}
+
+struct Robot {
+    battery_centivolts: u16,
+}
+
+impl TelemetrySource for Robot {
+    fn read_sensors(&mut self, sensors: &mut Sensors) {
+        let _ = sensors.push(Sensor::external_voltage(0, self.battery_centivolts));
+    }
+}
+
+#[test]
+fn hopping_replies_with_telemetry() {
+    const PACKETS: u32 = 8;
+
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    let mut receiver = Receiver::bound(RECEIVER_ID, &bind_result(), sim.now());
+    let mut robot = Robot {
+        battery_centivolts: 1260,
+    };
+    send_hopping(&sim, sim.now().add_micros(10_000), PACKETS);
+
+    for i in 0..PACKETS {
+        robot.battery_centivolts -= 10;
+        receiver.update_telemetry(&mut robot);
+        assert_eq!(
+            radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+            Ok(Output::Channels(hopping_sticks(i)))
+        );
+    }
+    // The reply to the last packet is sent on the next poll
+    assert!(matches!(
+        radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+        Ok(Output::Failsafe(_))
+    ));
+
+    // Each reply is sent on the channel the packet it answers was received on, and carries the
+    // readings from when that packet arrived
+    let transmissions = sim.transmissions();
+    assert_eq!(transmissions.len(), PACKETS as usize);
+    for (i, transmission) in transmissions.iter().enumerate() {
+        assert_eq!(transmission.channel, HOP_TABLE[i % NUM_HOP_CHANNELS]);
+        let packet = TelemetryPacket::from_frame(&transmission.frame).unwrap();
+        assert_eq!(packet.transmitter_id(), TRANSMITTER_ID);
+        assert_eq!(packet.receiver_id(), RECEIVER_ID);
+        assert_eq!(
+            packet.sensors().as_slice(),
+            [
+                Sensor::external_voltage(0, 1250 - i as u16 * 10),
+                Sensor::rssi(Rssi::from_raw(DEFAULT_RSSI)),
+                Sensor::error_rate(0),
+            ]
+        );
+    }
+}
+
+#[test]
+fn hopping_ignores_foreign_packets() {
+    const PACKETS: u32 = 8;
+
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    let mut receiver = Receiver::bound(RECEIVER_ID, &bind_result(), sim.now());
+    let start = sim.now().add_micros(10_000);
+    send_hopping(&sim, start, PACKETS);
+
+    // Another radio on the channel the receiver is waiting on, halfway between each hop, either
+    // bound to another receiver or using another transmitter ID
+    for i in 0..PACKETS {
+        let (transmitter_id, receiver_id) = if i % 2 == 0 {
+            (TRANSMITTER_ID, RECEIVER_ID ^ 1)
+        } else {
+            (TRANSMITTER_ID ^ 1, RECEIVER_ID)
+        };
+        let packet = SticksPacket::from_channels(
+            transmitter_id,
+            receiver_id,
+            Channels::from_slice(&[2000; 14]).unwrap(),
+        );
+        sim.send(
+            start.add_micros(i * HOP_PERIOD_US + HOP_PERIOD_US / 2),
+            HOP_TABLE[(i as usize + 1) % NUM_HOP_CHANNELS],
+            TransmitterPacket::Sticks(packet).to_frame(),
+        );
+    }
+
+    for i in 0..PACKETS {
+        assert_eq!(
+            radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+            Ok(Output::Channels(hopping_sticks(i)))
+        );
+    }
+    assert!(matches!(
+        radio.poll_receiver(&mut receiver, sim.delay(), &sim),
+        Ok(Output::Failsafe(_))
+    ));
+
+    let stats = receiver.link_stats().snapshot(sim.now());
+    assert_eq!(stats.foreign_packets, PACKETS);
+}
@@ -0,0 +1,299 @@
+mod common;
+
+use afhds2::{
+    store::{
+        ConfigStore, MemoryError, MemoryStorage, Record, StoreError, FIRST_APPLICATION_KEY,
+        MAX_RECORD_SIZE,
+    },
+    ChannelCount, Failsafe, ModelMemory,
+};
+use common::*;
+
+const SECTOR_SIZE: usize = 256;
+
+type Storage = MemoryStorage<SECTOR_SIZE>;
+
+/// Stick trims, as saved by firmware that only supported the first four channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TrimsV1([i8; 4]);
+
+impl Record for TrimsV1 {
+    const KEY: u8 = FIRST_APPLICATION_KEY;
+    const VERSION: u8 = 1;
+
+    fn encode(&self, bytes: &mut [u8; MAX_RECORD_SIZE]) -> usize {
+        for (byte, trim) in bytes.iter_mut().zip(self.0) {
+            *byte = trim as u8;
+        }
+        4
+    }
+
+    fn decode(_version: u8, bytes: &[u8]) -> Option<Self> {
+        let bytes: [u8; 4] = bytes.try_into().ok()?;
+        Some(Self(bytes.map(|byte| byte as i8)))
+    }
+}
+
+/// Stick trims, as saved by newer firmware with a trim for every channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Trims([i16; 14]);
+
+impl Record for Trims {
+    const KEY: u8 = FIRST_APPLICATION_KEY;
+    const VERSION: u8 = 2;
+
+    fn encode(&self, bytes: &mut [u8; MAX_RECORD_SIZE]) -> usize {
+        for (bytes, trim) in bytes.chunks_exact_mut(2).zip(self.0) {
+            bytes.copy_from_slice(&trim.to_le_bytes());
+        }
+        self.0.len() * 2
+    }
+
+    fn decode(version: u8, bytes: &[u8]) -> Option<Self> {
+        let mut trims = [0; 14];
+        match version {
+            1 => {
+                for (trim, byte) in trims.iter_mut().zip(bytes) {
+                    *trim = i16::from(*byte as i8);
+                }
+            }
+            2 => {
+                for (trim, bytes) in trims.iter_mut().zip(bytes.chunks_exact(2)) {
+                    *trim = i16::from_le_bytes([bytes[0], bytes[1]]);
+                }
+            }
+            _ => return None,
+        }
+        Some(Self(trims))
+    }
+}
+
+/// A single byte setting, small enough to save many times over in one sector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Counter(u8);
+
+impl Record for Counter {
+    const KEY: u8 = FIRST_APPLICATION_KEY + 1;
+    const VERSION: u8 = 1;
+
+    fn encode(&self, bytes: &mut [u8; MAX_RECORD_SIZE]) -> usize {
+        bytes[0] = self.0;
+        1
+    }
+
+    fn decode(_version: u8, bytes: &[u8]) -> Option<Self> {
+        Some(Self(*bytes.first()?))
+    }
+}
+
+/// Far too large to fit in a sector alongside anything else
+struct Blob;
+
+impl Record for Blob {
+    const KEY: u8 = FIRST_APPLICATION_KEY + 2;
+    const VERSION: u8 = 1;
+
+    fn encode(&self, _bytes: &mut [u8; MAX_RECORD_SIZE]) -> usize {
+        MAX_RECORD_SIZE
+    }
+
+    fn decode(_version: u8, _bytes: &[u8]) -> Option<Self> {
+        Some(Self)
+    }
+}
+
+struct BadKey;
+
+impl Record for BadKey {
+    const KEY: u8 = 0xF0;
+    const VERSION: u8 = 1;
+
+    fn encode(&self, _bytes: &mut [u8; MAX_RECORD_SIZE]) -> usize {
+        0
+    }
+
+    fn decode(_version: u8, _bytes: &[u8]) -> Option<Self> {
+        Some(Self)
+    }
+}
+
+struct BadVersion;
+
+impl Record for BadVersion {
+    const KEY: u8 = FIRST_APPLICATION_KEY + 3;
+    const VERSION: u8 = u8::MAX;
+
+    fn encode(&self, _bytes: &mut [u8; MAX_RECORD_SIZE]) -> usize {
+        0
+    }
+
+    fn decode(_version: u8, _bytes: &[u8]) -> Option<Self> {
+        Some(Self)
+    }
+}
+
+fn reopen(store: ConfigStore<Storage>) -> ConfigStore<Storage> {
+    let mut storage = store.into_storage();
+    storage.restore_power();
+    ConfigStore::open(storage).unwrap()
+}
+
+#[test]
+fn records_survive_reopening() {
+    let mut store = ConfigStore::open(Storage::new()).unwrap();
+    assert_eq!(store.load::<Counter>(), Ok(None));
+
+    store.save(&Counter(1)).unwrap();
+    store.save(&Trims([-3; 14])).unwrap();
+    store.save(&Counter(2)).unwrap();
+    assert_eq!(store.load(), Ok(Some(Counter(2))));
+
+    let mut store = reopen(store);
+    assert_eq!(store.load(), Ok(Some(Counter(2))));
+    assert_eq!(store.load(), Ok(Some(Trims([-3; 14]))));
+
+    store.remove::<Counter>().unwrap();
+    assert_eq!(store.load::<Counter>(), Ok(None));
+    let mut store = reopen(store);
+    assert_eq!(store.load::<Counter>(), Ok(None));
+    assert_eq!(store.load(), Ok(Some(Trims([-3; 14]))));
+}
+
+#[test]
+fn unchanged_records_are_not_written() {
+    let mut store = ConfigStore::open(Storage::new()).unwrap();
+    store.save(&Counter(1)).unwrap();
+    let used = store.used_bytes();
+
+    store.save(&Counter(1)).unwrap();
+    assert_eq!(store.used_bytes(), used);
+    store.remove::<Trims>().unwrap();
+    assert_eq!(store.used_bytes(), used);
+}
+
+#[test]
+fn full_sectors_are_compacted_in_turn() {
+    let mut store = ConfigStore::open(Storage::new()).unwrap();
+    store.save(&Trims([7; 14])).unwrap();
+
+    for i in 0..=u8::MAX {
+        store.save(&Counter(i)).unwrap();
+    }
+    assert_eq!(store.load(), Ok(Some(Counter(u8::MAX))));
+    assert_eq!(store.load(), Ok(Some(Trims([7; 14]))));
+
+    // Both sectors are worn evenly, each erase making room for many saves
+    let storage = store.into_storage();
+    let erases = [storage.erase_count(0), storage.erase_count(1)];
+    assert!(erases[0].abs_diff(erases[1]) <= 1, "{erases:?}");
+    assert!(erases[0] + erases[1] < 40, "{erases:?}");
+
+    let mut store = ConfigStore::open(storage).unwrap();
+    assert_eq!(store.load(), Ok(Some(Counter(u8::MAX))));
+    assert_eq!(store.load(), Ok(Some(Trims([7; 14]))));
+}
+
+#[test]
+fn torn_records_are_ignored() {
+    let mut store = ConfigStore::open(Storage::new()).unwrap();
+    store.save(&Trims([1; 14])).unwrap();
+
+    // Lose power half way through writing the new trims
+    let mut storage = store.into_storage();
+    storage.cut_power_after(16);
+    let mut store = ConfigStore::open(storage).unwrap();
+    assert_eq!(
+        store.save(&Trims([2; 14])),
+        Err(StoreError::Storage(MemoryError::PowerLost))
+    );
+
+    let mut store = reopen(store);
+    assert_eq!(store.load(), Ok(Some(Trims([1; 14]))));
+    store.save(&Trims([3; 14])).unwrap();
+    let mut store = reopen(store);
+    assert_eq!(store.load(), Ok(Some(Trims([3; 14]))));
+}
+
+#[test]
+fn power_loss_while_compacting_keeps_the_old_sector() {
+    let mut store = ConfigStore::open(Storage::new()).unwrap();
+    store.save(&Trims([4; 14])).unwrap();
+    let mut i = 0;
+    while store.active_sector() == 0 {
+        let mut storage = reopen(store).into_storage();
+        // Enough power for the next save, unless it has to move to the other sector first
+        storage.cut_power_after(12);
+        store = ConfigStore::open(storage).unwrap();
+        match store.save(&Counter(i)) {
+            Ok(()) => i += 1,
+            Err(StoreError::Storage(MemoryError::PowerLost)) => break,
+            Err(e) => panic!("{e:?}"),
+        }
+    }
+    assert!(i > 0);
+
+    let mut store = reopen(store);
+    assert_eq!(store.active_sector(), 0);
+    assert_eq!(store.load(), Ok(Some(Counter(i - 1))));
+    assert_eq!(store.load(), Ok(Some(Trims([4; 14]))));
+
+    store.save(&Counter(i)).unwrap();
+    assert_eq!(store.active_sector(), 1);
+    let mut store = reopen(store);
+    assert_eq!(store.load(), Ok(Some(Counter(i))));
+    assert_eq!(store.load(), Ok(Some(Trims([4; 14]))));
+}
+
+#[test]
+fn older_records_are_migrated() {
+    let mut store = ConfigStore::open(Storage::new()).unwrap();
+    store.save(&TrimsV1([1, -2, 3, -4])).unwrap();
+
+    // After updating the firmware
+    let mut store = reopen(store);
+    let mut expected = [0; 14];
+    expected[..4].copy_from_slice(&[1, -2, 3, -4]);
+    assert_eq!(store.load(), Ok(Some(Trims(expected))));
+
+    // The migrated record is saved in the new version, which the old firmware cannot read
+    let mut store = reopen(store);
+    assert_eq!(store.load::<TrimsV1>(), Ok(None));
+    assert_eq!(store.load(), Ok(Some(Trims(expected))));
+}
+
+#[test]
+fn invalid_records_are_rejected() {
+    let mut store = ConfigStore::open(MemoryStorage::<128>::new()).unwrap();
+    assert_eq!(store.save(&BadKey), Err(StoreError::InvalidKey));
+    assert_eq!(store.save(&BadVersion), Err(StoreError::InvalidVersion));
+    assert_eq!(store.save(&Blob), Err(StoreError::Full));
+
+    store.save(&Counter(9)).unwrap();
+    assert_eq!(store.load(), Ok(Some(Counter(9))));
+}
+
+#[test]
+fn model_memory_round_trip() {
+    let mut models = ModelMemory::new();
+    models.add(bind_result()).unwrap();
+    let model = models.get_mut(TRANSMITTER_ID).unwrap();
+    let mut failsafe = [None; 14];
+    failsafe[0] = Some(1000);
+    failsafe[2] = Some(2000);
+    failsafe[13] = Some(1500);
+    model.failsafe = Failsafe::new(failsafe);
+    model.channel_count = ChannelCount::Eighteen;
+    let mut other = bind_result();
+    other.transmitter_id = 0x0BAD_F00D;
+    other.options = [0x5A; 10];
+    models.add(other).unwrap();
+    models.select(TRANSMITTER_ID);
+
+    let mut store = ConfigStore::open(MemoryStorage::<1024>::new()).unwrap();
+    store.save(&models).unwrap();
+    let mut store = ConfigStore::open(store.into_storage()).unwrap();
+    assert_eq!(store.load(), Ok(Some(models)));
+
+    store.save(&ModelMemory::new()).unwrap();
+    assert_eq!(store.load(), Ok(Some(ModelMemory::new())));
+}
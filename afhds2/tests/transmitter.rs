@@ -0,0 +1,190 @@
+mod common;
+
+use afhds2::{
+    bind::BIND_CHANNELS,
+    hopping::HOP_PERIOD_US,
+    packet::{
+        BindPacket, BindPacketType, TelemetryPacket, TransmitterPacket, NUM_HOP_CHANNELS,
+        UNKNOWN_RECEIVER_ID,
+    },
+    sim::SimRadio,
+    telemetry::{Sensor, Sensors},
+    time::Instant,
+    Afhds2, Channels, Transmitter, TransmitterOutput, BIND_CONFIRMATIONS, MAX_HOP_CHANNEL,
+    MIN_HOP_CHANNEL,
+};
+use common::*;
+
+const SEED: u32 = 0xC0FF_EE00;
+
+/// When a reply to a packet is sent, giving the transmitter time to finish sending and retune
+const REPLY_DELAY_US: u32 = 2_000;
+
+/// How late a packet can be sent without drifting off the cadence
+const SLOT_JITTER_US: u32 = 200;
+
+fn telemetry(transmitter_id: u32) -> TelemetryPacket {
+    let mut sensors = Sensors::new();
+    sensors.push(Sensor::internal_voltage(512)).unwrap();
+    TelemetryPacket::new(transmitter_id, RECEIVER_ID, sensors)
+}
+
+/// Check that packet `index` was sent in its slot, allowing for the time it takes to notice that
+/// the previous receive timed out
+fn assert_on_cadence(first: Instant, index: usize, at: Instant) {
+    let slot = first.add_micros(index as u32 * HOP_PERIOD_US);
+    assert!(slot.micros_since(at) == 0, "packet {index} sent early");
+    assert!(
+        at.micros_since(slot) < SLOT_JITTER_US as u64,
+        "packet {index} sent {}us late",
+        at.micros_since(slot)
+    );
+}
+
+#[test]
+fn hop_table_is_unique_and_in_range() {
+    for seed in 0..64 {
+        let transmitter = Transmitter::bind(seed, SimRadio::new().now());
+        let hop_table = transmitter.hop_table();
+
+        for (i, &channel) in hop_table.iter().enumerate() {
+            assert!((MIN_HOP_CHANNEL..=MAX_HOP_CHANNEL).contains(&channel));
+            assert!(!hop_table[..i].contains(&channel), "{hop_table:02x?}");
+        }
+        assert_ne!(transmitter.transmitter_id(), UNKNOWN_RECEIVER_ID);
+        assert_eq!(
+            Transmitter::bind(seed, SimRadio::new().now()).hop_table(),
+            hop_table
+        );
+    }
+}
+
+#[test]
+fn bind_completes_with_receiver() {
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    let start = sim.now();
+    let mut transmitter = Transmitter::bind(SEED, start);
+    let transmitter_id = transmitter.transmitter_id();
+
+    // The receiver replies to the second announcement
+    let reply = TransmitterPacket::Bind(BindPacket::new(
+        BindPacketType::Bind2,
+        transmitter_id,
+        RECEIVER_ID,
+        0x01,
+        [0xFF; NUM_HOP_CHANNELS],
+        [0xFF; 10],
+    ))
+    .to_frame();
+    sim.send(
+        start.add_micros(HOP_PERIOD_US + REPLY_DELAY_US),
+        BIND_CHANNELS[1],
+        reply,
+    );
+
+    assert_eq!(
+        radio.poll_transmitter(&mut transmitter, sim.delay(), &sim),
+        Ok(TransmitterOutput::Bound {
+            receiver_id: RECEIVER_ID
+        })
+    );
+    assert!(transmitter.is_bound());
+    assert_eq!(transmitter.receiver_id(), Some(RECEIVER_ID));
+
+    let transmissions = sim.transmissions();
+    assert_eq!(transmissions.len(), 2 + BIND_CONFIRMATIONS as usize);
+    for (i, transmission) in transmissions.iter().enumerate() {
+        assert_eq!(transmission.channel, BIND_CHANNELS[i % BIND_CHANNELS.len()]);
+        let packet = BindPacket::from_bytes(transmission.frame.as_bytes()).unwrap();
+        assert_eq!(packet.transmitter_id(), transmitter_id);
+        assert_eq!(packet.hop_table(), transmitter.hop_table());
+        if i < 2 {
+            assert_eq!(packet.packet_type(), BindPacketType::Bind1);
+            assert_eq!(packet.receiver_id(), UNKNOWN_RECEIVER_ID);
+        } else {
+            assert_eq!(packet.packet_type(), BindPacketType::Bind2);
+            assert_eq!(packet.receiver_id(), RECEIVER_ID);
+        }
+    }
+}
+
+#[test]
+fn sticks_follow_the_hop_sequence() {
+    const PACKETS: usize = 40;
+
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    let mut transmitter = Transmitter::bound(SEED, RECEIVER_ID, sim.now());
+
+    for i in 0..PACKETS {
+        transmitter.set_channels(Channels::from_slice(&[1000 + i as u16; 14]).unwrap());
+        assert_eq!(
+            radio.poll_transmitter(&mut transmitter, sim.delay(), &sim),
+            Ok(TransmitterOutput::Sent)
+        );
+    }
+
+    // The first packet is queued as soon as the transmitter is created, and each one after that
+    // carries the sticks set before the poll that sent it
+    let transmissions = sim.transmissions();
+    assert_eq!(transmissions.len(), PACKETS);
+    for (i, transmission) in transmissions.iter().enumerate() {
+        assert_eq!(
+            transmission.channel,
+            transmitter.hop_table()[i % NUM_HOP_CHANNELS]
+        );
+        assert_on_cadence(transmissions[0].at, i, transmission.at);
+        let Ok(TransmitterPacket::Sticks(packet)) =
+            TransmitterPacket::from_frame(&transmission.frame)
+        else {
+            panic!("expected a sticks packet");
+        };
+        assert_eq!(packet.transmitter_id(), transmitter.transmitter_id());
+        assert_eq!(packet.receiver_id(), RECEIVER_ID);
+        let sticks = if i == 0 { 1500 } else { 1000 + i as u16 };
+        assert_eq!(packet.sticks(), &[sticks; 14]);
+    }
+}
+
+#[test]
+fn telemetry_is_received_between_packets() {
+    let sim = SimRadio::new();
+    let mut radio = Afhds2::new(sim.spi(), sim.gpio());
+    radio.configure_radio(sim.delay()).unwrap();
+    let start = sim.now();
+    let mut transmitter = Transmitter::bound(SEED, RECEIVER_ID, start);
+    let telemetry = telemetry(transmitter.transmitter_id());
+    let channel = transmitter.hop_table()[1];
+    sim.send(
+        start.add_micros(HOP_PERIOD_US + REPLY_DELAY_US),
+        channel,
+        telemetry.to_frame(),
+    );
+
+    let outputs: Vec<_> = (0..4)
+        .map(|_| {
+            radio
+                .poll_transmitter(&mut transmitter, sim.delay(), &sim)
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(
+        outputs,
+        [
+            TransmitterOutput::Sent,
+            TransmitterOutput::Sent,
+            TransmitterOutput::Telemetry(telemetry),
+            TransmitterOutput::Sent,
+        ]
+    );
+
+    // Receiving the reply does not disturb the cadence of the packets that follow
+    let transmissions = sim.transmissions();
+    assert_eq!(transmissions.len(), 3);
+    for (i, transmission) in transmissions.iter().enumerate() {
+        assert_on_cadence(transmissions[0].at, i, transmission.at);
+    }
+}
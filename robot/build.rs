@@ -1,4 +1,13 @@
+use std::{env, fs, path::PathBuf};
+
 fn main() {
+    // Put memory.x where the linker can find it, rather than using the one from embassy-stm32,
+    // so that the flash sectors used for storage are kept free
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    fs::copy("memory.x", out.join("memory.x")).unwrap();
+    println!("cargo:rustc-link-search={}", out.display());
+    println!("cargo:rerun-if-changed=memory.x");
+
     println!("cargo:rustc-link-arg-bins=--nmagic");
     println!("cargo:rustc-link-arg-bins=-Tlink.x");
     println!("cargo:rustc-link-arg-bins=-Tdefmt.x");
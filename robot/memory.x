@@ -0,0 +1,6 @@
+MEMORY
+{
+  /* STM32F411RE, without flash sectors 6 and 7 which hold the configuration store */
+  FLASH : ORIGIN = 0x08000000, LENGTH = 256K
+  RAM : ORIGIN = 0x20000000, LENGTH = 128K
+}
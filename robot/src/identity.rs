@@ -0,0 +1,25 @@
+//! The identity the robot binds with
+
+use afhds2::ReceiverId;
+
+/// Where the 96 bit unique device ID is mapped on the STM32F411, see RM0383 section 24.2
+const UID_ADDRESS: usize = 0x1FFF_7A10;
+
+/// The number of bytes in the unique device ID
+const UID_LEN: usize = 12;
+
+/// Read the unique device ID programmed into the STM32 at the factory
+pub fn uid() -> [u8; UID_LEN] {
+    let mut uid = [0; UID_LEN];
+    for (i, byte) in uid.iter_mut().enumerate() {
+        // SAFETY: the unique device ID is a read-only system memory region present on every
+        // STM32F411, and reading it has no side effects
+        *byte = unsafe { core::ptr::read_volatile((UID_ADDRESS + i) as *const u8) };
+    }
+    uid
+}
+
+/// The receiver ID of this robot, the same every time it boots and across reflashes
+pub fn receiver_id() -> ReceiverId {
+    ReceiverId::from_seed(&uid())
+}
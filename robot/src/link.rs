@@ -0,0 +1,63 @@
+//! Receiving from the transmitter, and remembering every transmitter the robot is bound to
+//!
+//! With no transmitters stored the robot starts out binding, otherwise it searches for any of the
+//! stored transmitters and follows the first one it hears from. Every bind, whichever transmitter
+//! was last followed and the failsafe positions it sends are saved, so the robot comes back to it
+//! after a power cycle.
+
+use afhds2::{time::Instant, ModelMemory, Output, Receiver, ReceiverId};
+use defmt::{error, info, warn, Debug2Format};
+use embassy_time::Delay;
+
+use crate::{radio::Radio, storage, storage::Store};
+
+/// The current time, as used by the [`Receiver`]
+fn now() -> Instant {
+    Instant::from_micros(embassy_time::Instant::now().as_micros())
+}
+
+pub async fn run(
+    mut radio: Radio,
+    receiver_id: ReceiverId,
+    mut models: ModelMemory,
+    mut store: Option<Store>,
+) -> ! {
+    let mut receiver = Receiver::from_models(receiver_id.raw(), &models, now());
+    loop {
+        let mut changed = false;
+        match radio.poll_receiver(&mut receiver, Delay, &now).await {
+            Ok(Output::Bound(bind)) => {
+                info!("bound to transmitter {=u32:#010x}", bind.transmitter_id);
+                changed = models.add(bind).is_ok();
+                if !changed {
+                    warn!("no room left to remember transmitter");
+                }
+            }
+            Ok(Output::Selected(transmitter_id)) => {
+                info!("following transmitter {=u32:#010x}", transmitter_id);
+                changed = models.select(transmitter_id);
+            }
+            // Nothing on the robot is driven from the channels yet
+            Ok(Output::Channels(_)) => {}
+            Ok(Output::Failsafe(_)) => warn!("link lost, failsafe engaged"),
+            Err(e) => error!("receiver failed: {}", Debug2Format(&e)),
+        }
+
+        // Pick up the failsafe positions the transmitter sends, only writing to flash when they
+        // actually change
+        if let Some(model) = receiver.model() {
+            if let Some(stored) = models.get_mut(model.transmitter_id()) {
+                if *stored != model {
+                    *stored = model;
+                    changed = true;
+                }
+            }
+        }
+
+        if changed {
+            if let Some(store) = store.as_mut() {
+                storage::save_models(store, &models);
+            }
+        }
+    }
+}
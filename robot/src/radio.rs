@@ -1,80 +1,62 @@
-use defmt::Format;
-use nom::{
-    branch::alt,
-    bytes::complete::tag,
-    number::complete::{le_u16, le_u32, le_u8},
-    IResult,
+//! Wiring of the A7105 on the robot
+
+use afhds2::Afhds2;
+use embassy_embedded_hal::shared_bus::asynch::spi::SpiDevice;
+use embassy_stm32::{
+    exti::ExtiInput,
+    gpio::{Input, Level, Output, Pull, Speed},
+    peripherals::{DMA2_CH2, DMA2_CH3, EXTI9, PA5, PA6, PA7, PA9, PB6, SPI1},
+    spi::{self, Spi},
+    time::Hertz,
 };
-
-const NUM_CONTROL_CHANNELS: usize = 14;
-
-const PACKET_ID_BIND1: u8 = 0xBB;
-const PACKET_ID_BIND2: u8 = 0xBC;
-const PACKET_ID_STICKS: u8 = 0x58;
-const PACKET_ID_FAILSAFE: u8 = 0x56;
-
-#[derive(Format, Debug, Format, Clone, PartialEq)]
-pub enum TransmitterPacket {
-    Sticks(SticksPacket),
-    Bind(BindPacket),
-}
-
-impl TransmitterPacket {
-    pub fn from_bytes(bytes: &[u8]) -> IResult<&[u8], Self> {
-        alt((SticksPacket::from_bytes, BindPacket::from_bytes))(bytes)
-    }
-}
-
-#[derive(Format, Debug, Format, Clone, PartialEq)]
-pub struct SticksPacket {
-    transmitter_id: u32,
-    receiver_id: u32,
-    sticks: [u16; NUM_CONTROL_CHANNELS],
+use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+use static_cell::StaticCell;
+
+/// The A7105 supports SPI clocks up to 10MHz
+const SPI_FREQUENCY: Hertz = Hertz(8_000_000);
+
+type RadioBus = Spi<'static, SPI1, DMA2_CH3, DMA2_CH2>;
+
+/// The AFHDS2A radio on the robot
+pub type Radio = Afhds2<
+    SpiDevice<'static, NoopRawMutex, RadioBus, Output<'static, PB6>>,
+    ExtiInput<'static, PA9>,
+>;
+
+/// The peripherals the A7105 is connected to
+pub struct RadioPins {
+    pub spi: SPI1,
+    pub sck: PA5,
+    pub mosi: PA7,
+    pub miso: PA6,
+    pub cs: PB6,
+    pub gio2: PA9,
+    pub gio2_exti: EXTI9,
+    pub tx_dma: DMA2_CH3,
+    pub rx_dma: DMA2_CH2,
 }
 
-impl SticksPacket {
-    pub fn from_bytes(bytes: &[u8]) -> IResult<&[u8], TransmitterPacket> {
-        let (bytes, _) = tag(&[PACKET_ID_STICKS])(bytes)?;
-        let (bytes, transmitter_id) = le_u32(bytes)?;
-        let (mut bytes, receiver_id) = le_u32(bytes)?;
-        let mut sticks = [0u16; NUM_CONTROL_CHANNELS];
-
-        for i in 0..NUM_CONTROL_CHANNELS {
-            (bytes, sticks[i]) = le_u16(bytes)?;
-        }
-
-        Ok((
-            bytes,
-            TransmitterPacket::Sticks(SticksPacket {
-                transmitter_id,
-                receiver_id,
-                sticks,
-            }),
-        ))
-    }
-}
-
-#[derive(Format, Debug, Format, Clone, PartialEq)]
-pub struct BindPacket {
-    transmitter_id: u32,
-    receiver_id: u32,
-    stage: u8,
-}
-
-impl BindPacket {
-    pub fn from_bytes(bytes: &[u8]) -> IResult<&[u8], TransmitterPacket> {
-        let (bytes, _) = alt((tag(&[PACKET_ID_BIND1]), tag(&[PACKET_ID_BIND2])))(bytes)?;
-        let (bytes, transmitter_id) = le_u32(bytes)?;
-        let (bytes, receiver_id) = le_u32(bytes)?;
-        let (bytes, stage) = le_u8(bytes)?;
-
-        Ok((
-            bytes,
-            TransmitterPacket::Bind(BindPacket {
-                transmitter_id,
-                receiver_id,
-                stage,
-            }),
-        ))
-    }
+/// Set up the SPI bus and GPIO pins for the radio
+///
+/// This can only be called once, and does not initialize the radio itself.
+pub fn init(pins: RadioPins) -> Radio {
+    static BUS: StaticCell<Mutex<NoopRawMutex, RadioBus>> = StaticCell::new();
+
+    let mut config = spi::Config::default();
+    config.frequency = SPI_FREQUENCY;
+    let bus = Spi::new(
+        pins.spi,
+        pins.sck,
+        pins.mosi,
+        pins.miso,
+        pins.tx_dma,
+        pins.rx_dma,
+        config,
+    );
+    let bus = BUS.init(Mutex::new(bus));
+
+    let cs = Output::new(pins.cs, Level::High, Speed::VeryHigh);
+    let gio2 = ExtiInput::new(Input::new(pins.gio2, Pull::None), pins.gio2_exti);
+
+    Afhds2::new(SpiDevice::new(bus, cs), gio2)
 }
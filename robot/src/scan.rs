@@ -0,0 +1,41 @@
+//! A firmware mode that repeatedly sweeps the 2.4GHz band, logging how busy each channel is
+//!
+//! This is built with the `scan` feature, and is used to check for congestion at an event before
+//! a match. Each sweep logs one line per channel followed by a summary.
+
+use defmt::{error, info, Debug2Format};
+use embassy_time::{Delay, Duration, Timer};
+
+use crate::radio::Radio;
+
+/// How long to listen on each channel, so a full sweep takes around a third of a second
+const DWELL_US: u32 = 2_000;
+
+/// How long to wait between sweeps
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+pub async fn run(mut radio: Radio) -> ! {
+    loop {
+        match radio.spectrum_scan(Delay, DWELL_US).await {
+            Ok(report) => {
+                for (channel, occupancy) in report.channels() {
+                    info!(
+                        "scan channel={=u8:#04x} peak={}dBm average={}dBm busy={}",
+                        channel,
+                        occupancy.peak.dbm(),
+                        occupancy.average.dbm(),
+                        report.is_busy(channel),
+                    );
+                }
+                info!(
+                    "scan done busy_channels={} busiest={=u8:#04x}",
+                    report.busy_channels(),
+                    report.busiest(),
+                );
+            }
+            Err(e) => error!("scan failed: {}", Debug2Format(&e)),
+        }
+
+        Timer::after(SWEEP_INTERVAL).await;
+    }
+}
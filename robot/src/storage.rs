@@ -0,0 +1,83 @@
+//! Non-volatile storage of the robot's settings, in the internal flash
+
+use afhds2::{
+    store::{ConfigStore, Storage},
+    ModelMemory,
+};
+use defmt::error;
+use embassy_stm32::{
+    flash::{Blocking, Error, Flash, WRITE_SIZE},
+    peripherals::FLASH,
+};
+
+/// Where flash sectors 6 and 7 start, from the start of flash, which memory.x leaves free
+const STORE_OFFSET: u32 = 0x0004_0000;
+
+/// Sectors 6 and 7 are both 128K
+const SECTOR_SIZE: u32 = 128 * 1024;
+
+/// The configuration store of the robot
+pub type Store = ConfigStore<FlashStorage>;
+
+/// The two flash sectors set aside for the [`Store`]
+pub struct FlashStorage {
+    flash: Flash<'static, Blocking>,
+}
+
+impl FlashStorage {
+    pub fn new(flash: FLASH) -> Self {
+        Self {
+            flash: Flash::new_blocking(flash),
+        }
+    }
+}
+
+impl Storage for FlashStorage {
+    type Error = Error;
+
+    const SECTOR_SIZE: u32 = SECTOR_SIZE;
+    const WRITE_SIZE: u32 = WRITE_SIZE as u32;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Error> {
+        self.flash.blocking_read(STORE_OFFSET + offset, bytes)
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Error> {
+        self.flash.blocking_write(STORE_OFFSET + offset, bytes)
+    }
+
+    fn erase(&mut self, sector: u8) -> Result<(), Error> {
+        let start = STORE_OFFSET + u32::from(sector) * SECTOR_SIZE;
+        self.flash.blocking_erase(start, start + SECTOR_SIZE)
+    }
+}
+
+/// Open the store, erasing it if this is the first boot
+pub fn open(flash: FLASH) -> Option<Store> {
+    match ConfigStore::open(FlashStorage::new(flash)) {
+        Ok(store) => Some(store),
+        Err(e) => {
+            error!("failed to open config store: {}", e);
+            None
+        }
+    }
+}
+
+/// The transmitters the robot has been bound to, if any
+pub fn load_models(store: &mut Store) -> ModelMemory {
+    match store.load() {
+        Ok(models) => models.unwrap_or_default(),
+        Err(e) => {
+            error!("failed to load bound transmitters: {}", e);
+            ModelMemory::new()
+        }
+    }
+}
+
+/// Remember the transmitters the robot has been bound to, for the next boot
+#[cfg(not(feature = "scan"))]
+pub fn save_models(store: &mut Store, models: &ModelMemory) {
+    if let Err(e) = store.save(models) {
+        error!("failed to save bound transmitters: {}", e);
+    }
+}